@@ -0,0 +1,281 @@
+use crate::{
+    apu_channels::{
+        dmc_channel::DmcChannel,
+        frame_counter::{FrameCounter, FrameStep},
+        noise_channel::NoiseChannel,
+        pulse_channel::PulseChannel,
+        sweep_unit::Negate,
+        triangle_channel::TriangleChannel,
+    },
+    rom::Timing,
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const CLOCK_RATE: u32 = 1789773; // NTSC CPU clock
+pub const SAMPLE_RATE: u32 = 44100;
+// samples nobody took are dropped past one second's worth
+const MAX_BUFFERED_SAMPLES: usize = SAMPLE_RATE as usize;
+
+pub struct Apu {
+    pulse1: PulseChannel,
+    pulse2: PulseChannel,
+    triangle: TriangleChannel,
+    noise: NoiseChannel,
+    dmc: DmcChannel,
+    frame_counter: FrameCounter,
+
+    // pulse timers run at half the CPU clock
+    odd_cycle: bool,
+    // CPU cycles times SAMPLE_RATE since the last sample
+    sample_clock: u32,
+    buffer: Vec<i16>,
+}
+
+impl Apu {
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            pulse1: PulseChannel::new(Negate::OnesComplement),
+            pulse2: PulseChannel::new(Negate::TwosComplement),
+            triangle: TriangleChannel::new(),
+            noise: NoiseChannel::new(timing),
+            dmc: DmcChannel::new(timing),
+            frame_counter: FrameCounter::new(),
+            odd_cycle: false,
+            sample_clock: 0,
+            buffer: Vec::new(),
+        }
+    }
+
+    // The non-linear mixer from https://www.nesdev.org/wiki/APU_Mixer,
+    // 0.0..=1.0
+    fn generate_sample(&self) -> f32 {
+        let pulse = (self.pulse1.generate_sample() + self.pulse2.generate_sample()) as f32;
+        let pulse_out = if pulse == 0.0 {
+            0.0
+        } else {
+            95.88 / (8128.0 / pulse + 100.0)
+        };
+
+        let tnd = self.triangle.generate_sample() as f32 / 8227.0
+            + self.noise.generate_sample() as f32 / 12241.0
+            + self.dmc.generate_sample() as f32 / 22638.0;
+        let tnd_out = if tnd == 0.0 {
+            0.0
+        } else {
+            159.79 / (1.0 / tnd + 100.0)
+        };
+
+        pulse_out + tnd_out
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        for _ in 0..cycles {
+            self.clock();
+        }
+    }
+
+    fn clock(&mut self) {
+        match self.frame_counter.tick() {
+            Some(FrameStep::Quarter) => self.clock_quarter_frame(),
+            Some(FrameStep::Half) => {
+                self.clock_quarter_frame();
+                self.clock_half_frame();
+            }
+            None => {}
+        }
+
+        self.triangle.tick_sequencer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        self.sample_clock += SAMPLE_RATE;
+        if self.sample_clock >= CLOCK_RATE {
+            self.sample_clock -= CLOCK_RATE;
+            if self.buffer.len() >= MAX_BUFFERED_SAMPLES {
+                self.buffer.drain(..MAX_BUFFERED_SAMPLES / 2);
+            }
+            self.buffer
+                .push((self.generate_sample() * i16::MAX as f32) as i16);
+        }
+    }
+
+    fn clock_quarter_frame(&mut self) {
+        self.pulse1.clock_quarter_frame();
+        self.pulse2.clock_quarter_frame();
+        self.triangle.clock_quarter_frame();
+        self.noise.clock_quarter_frame();
+    }
+
+    fn clock_half_frame(&mut self) {
+        self.pulse1.clock_half_frame();
+        self.pulse2.clock_half_frame();
+        self.triangle.clock_half_frame();
+        self.noise.clock_half_frame();
+    }
+
+    pub fn take_samples(&mut self) -> Vec<i16> {
+        let samples = self.buffer.clone();
+        self.buffer.clear();
+        samples
+    }
+
+    pub fn irq_pending(&self) -> bool {
+        self.frame_counter.irq_flag() || self.dmc.irq_flag()
+    }
+
+    // CPU address the DMC wants read into its sample buffer
+    pub fn dmc_sample_request(&self) -> Option<u16> {
+        self.dmc.sample_request()
+    }
+
+    pub fn load_dmc_sample(&mut self, data: u8) {
+        self.dmc.load_sample(data);
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8, cycles: usize) {
+        match addr {
+            0x4000..=0x4003 => self.pulse1.write_register(addr, data),
+            0x4004..=0x4007 => self.pulse2.write_register(addr, data),
+            0x4008..=0x400B => self.triangle.write_register(addr, data),
+            0x400C..=0x400F => self.noise.write_register(addr, data),
+            0x4010..=0x4013 => self.dmc.write_register(addr, data),
+            0x4015 => {
+                self.pulse1.set_enabled(data & 0b0000_0001 != 0);
+                self.pulse2.set_enabled(data & 0b0000_0010 != 0);
+                self.triangle.set_enabled(data & 0b0000_0100 != 0);
+                self.noise.set_enabled(data & 0b0000_1000 != 0);
+                self.dmc.set_enabled(data & 0b0001_0000 != 0);
+            }
+            0x4017 => self.frame_counter.write_control(data, cycles % 2 == 1),
+            _ => {}
+        }
+    }
+
+    // $4015: IF-D NT21, length counters and DMC still playing, with the
+    // frame and DMC IRQ flags. Bit 5 is open bus. Reading acknowledges the
+    // frame IRQ, but not the DMC's.
+    pub fn read_register(&mut self) -> u8 {
+        let status = (self.pulse1.is_length_non_zero() as u8)
+            | (self.pulse2.is_length_non_zero() as u8) << 1
+            | (self.triangle.is_length_non_zero() as u8) << 2
+            | (self.noise.is_length_non_zero() as u8) << 3
+            | (self.dmc.is_active() as u8) << 4
+            | (self.frame_counter.irq_flag() as u8) << 6
+            | (self.dmc.irq_flag() as u8) << 7;
+        self.frame_counter.clear_irq();
+        status
+    }
+}
+
+impl Snapshot for Apu {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.pulse1.save_state(writer);
+        self.pulse2.save_state(writer);
+        self.triangle.save_state(writer);
+        self.noise.save_state(writer);
+        self.dmc.save_state(writer);
+        self.frame_counter.save_state(writer);
+        writer.write_bool(self.odd_cycle);
+        writer.write_u32(self.sample_clock);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.pulse1.load_state(reader)?;
+        self.pulse2.load_state(reader)?;
+        self.triangle.load_state(reader)?;
+        self.noise.load_state(reader)?;
+        self.dmc.load_state(reader)?;
+        self.frame_counter.load_state(reader)?;
+        self.odd_cycle = reader.read_bool()?;
+        self.sample_clock = reader.read_u32()?.min(CLOCK_RATE - 1);
+        self.buffer.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_cycles(apu: &mut Apu, cycles: u32) {
+        for _ in 0..cycles / 100 {
+            apu.tick(100);
+        }
+        apu.tick((cycles % 100) as u8);
+    }
+
+    #[test]
+    fn test_samples_at_output_rate() {
+        let mut apu = Apu::new(Timing::NTSC);
+        run_cycles(&mut apu, CLOCK_RATE / 10);
+        let samples = apu.take_samples();
+        assert!(samples.len().abs_diff(SAMPLE_RATE as usize / 10) <= 1);
+        // an idle APU still outputs the triangle's level as a constant
+        assert!(samples.iter().all(|&sample| sample == samples[0]));
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn test_status_register() {
+        let mut apu = Apu::new(Timing::NTSC);
+        apu.write_register(0x4015, 0b0000_1101, 0);
+        apu.write_register(0x4003, 0b0000_1000, 0);
+        apu.write_register(0x4007, 0b0000_1000, 0);
+        apu.write_register(0x400F, 0b0001_1000, 0);
+        assert_eq!(apu.read_register(), 0b0000_1001);
+
+        // the noise length of 2 runs out after two half frames
+        run_cycles(&mut apu, 29829);
+        assert_eq!(apu.read_register(), 0b0100_0001);
+        // the read acknowledged the frame IRQ
+        assert_eq!(apu.read_register(), 0b0000_0001);
+        assert!(!apu.irq_pending());
+
+        apu.write_register(0x4015, 0, 0);
+        assert_eq!(apu.read_register(), 0);
+    }
+
+    #[test]
+    fn test_envelope_decays_on_quarter_frames() {
+        let mut apu = Apu::new(Timing::NTSC);
+        apu.write_register(0x4015, 0b0000_0001, 0);
+        // envelope with period 0, halted length
+        apu.write_register(0x4000, 0b1010_0000, 0);
+        apu.write_register(0x4002, 0xFD, 0);
+        apu.write_register(0x4003, 0b0000_1000, 0);
+
+        // quarter frames at 7457, 14913, 22371 and 29829: the start flag
+        // sets the level to 15, then it decays by one each time
+        run_cycles(&mut apu, 29829);
+        let peak = (0..64)
+            .map(|_| {
+                run_cycles(&mut apu, 64);
+                apu.pulse1.generate_sample()
+            })
+            .max();
+        assert_eq!(peak, Some(12));
+    }
+
+    #[test]
+    fn test_pulse_is_audible() {
+        let mut apu = Apu::new(Timing::NTSC);
+        apu.write_register(0x4015, 0b0000_0001, 0);
+        // 50% duty, constant volume 15, about 440Hz
+        apu.write_register(0x4000, 0b1011_1111, 0);
+        apu.write_register(0x4002, 0xFD, 0);
+        apu.write_register(0x4003, 0b0000_1000, 0);
+
+        run_cycles(&mut apu, CLOCK_RATE / 100);
+        let samples = apu.take_samples();
+        let low = *samples.iter().min().unwrap();
+        let high = samples.iter().filter(|&&sample| sample > low).count();
+        // roughly half of the samples are high
+        assert!(high > samples.len() * 2 / 5 && high < samples.len() * 3 / 5);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::{
+    rom::Timing,
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+// timer periods in CPU cycles
+const NTSC_RATES: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+const PAL_RATES: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+// The delta modulation channel plays 1-bit delta samples read from CPU
+// memory: each bit moves the 7-bit output level up or down by 2.
+pub struct DmcChannel {
+    irq_enabled: bool,
+    irq_flag: bool,
+    loop_sample: bool,
+    rates: &'static [u16; 16],
+    timer_load: u16,
+    timer: u16,
+
+    // memory reader
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    // output unit
+    shift_register: u8,
+    bits_remaining: u8,
+    silence: bool,
+    output_level: u8,
+}
+
+impl DmcChannel {
+    pub fn new(timing: Timing) -> Self {
+        let rates = match timing {
+            Timing::PAL => &PAL_RATES,
+            _ => &NTSC_RATES,
+        };
+        Self {
+            irq_enabled: false,
+            irq_flag: false,
+            loop_sample: false,
+            rates,
+            timer_load: rates[0] - 1,
+            timer: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            current_address: 0xC000,
+            bytes_remaining: 0,
+            sample_buffer: None,
+            shift_register: 0,
+            bits_remaining: 8,
+            silence: true,
+            output_level: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4010 => {
+                self.irq_enabled = data & 0b1000_0000 != 0;
+                if !self.irq_enabled {
+                    self.irq_flag = false;
+                }
+                self.loop_sample = data & 0b0100_0000 != 0;
+                self.timer_load = self.rates[(data & 0b1111) as usize] - 1;
+            }
+            0x4011 => self.output_level = data & 0b0111_1111,
+            0x4012 => self.sample_address = 0xC000 | ((data as u16) << 6),
+            0x4013 => self.sample_length = ((data as u16) << 4) | 1,
+            _ => {}
+        }
+    }
+
+    // $4015 bit 4; also acknowledges the IRQ
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq_flag = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    // every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_load;
+            self.clock_output();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_output(&mut self) {
+        if !self.silence {
+            if self.shift_register & 1 != 0 {
+                if self.output_level <= 125 {
+                    self.output_level += 2;
+                }
+            } else if self.output_level >= 2 {
+                self.output_level -= 2;
+            }
+        }
+        self.shift_register >>= 1;
+
+        self.bits_remaining -= 1;
+        if self.bits_remaining == 0 {
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(sample) => {
+                    self.silence = false;
+                    self.shift_register = sample;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    // address the memory reader wants fetched, when its buffer is empty
+    pub fn sample_request(&self) -> Option<u16> {
+        if self.sample_buffer.is_none() && self.bytes_remaining > 0 {
+            Some(self.current_address)
+        } else {
+            None
+        }
+    }
+
+    pub fn load_sample(&mut self, data: u8) {
+        self.sample_buffer = Some(data);
+        // wraps from $FFFF back to $8000
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.loop_sample {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    pub fn irq_flag(&self) -> bool {
+        self.irq_flag
+    }
+
+    // 0..=127
+    pub fn generate_sample(&self) -> u8 {
+        self.output_level
+    }
+}
+
+impl Snapshot for DmcChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.irq_enabled);
+        writer.write_bool(self.irq_flag);
+        writer.write_bool(self.loop_sample);
+        writer.write_u16(self.timer_load);
+        writer.write_u16(self.timer);
+        writer.write_u16(self.sample_address);
+        writer.write_u16(self.sample_length);
+        writer.write_u16(self.current_address);
+        writer.write_u16(self.bytes_remaining);
+        writer.write_bool(self.sample_buffer.is_some());
+        writer.write_u8(self.sample_buffer.unwrap_or(0));
+        writer.write_u8(self.shift_register);
+        writer.write_u8(self.bits_remaining);
+        writer.write_bool(self.silence);
+        writer.write_u8(self.output_level);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.irq_enabled = reader.read_bool()?;
+        self.irq_flag = reader.read_bool()?;
+        self.loop_sample = reader.read_bool()?;
+        self.timer_load = reader.read_u16()?;
+        self.timer = reader.read_u16()?;
+        self.sample_address = reader.read_u16()?;
+        self.sample_length = reader.read_u16()?;
+        self.current_address = reader.read_u16()?;
+        self.bytes_remaining = reader.read_u16()?;
+        let has_sample = reader.read_bool()?;
+        let sample = reader.read_u8()?;
+        self.sample_buffer = if has_sample { Some(sample) } else { None };
+        self.shift_register = reader.read_u8()?;
+        self.bits_remaining = reader.read_u8()?.clamp(1, 8);
+        self.silence = reader.read_bool()?;
+        self.output_level = reader.read_u8()? & 0x7F;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dmc_playing(control: u8, address: u8, length: u8) -> DmcChannel {
+        let mut dmc = DmcChannel::new(Timing::NTSC);
+        dmc.write_register(0x4010, control);
+        dmc.write_register(0x4012, address);
+        dmc.write_register(0x4013, length);
+        dmc.set_enabled(true);
+        dmc
+    }
+
+    #[test]
+    fn test_memory_reader() {
+        // $FFC0, 65 bytes: wraps around to $8000
+        let mut dmc = dmc_playing(0x80, 0xFF, 4);
+        for expected in (0xFFC0..=0xFFFF).chain([0x8000]) {
+            assert_eq!(dmc.sample_request(), Some(expected));
+            assert!(!dmc.irq_flag());
+            dmc.load_sample(0);
+            // the buffer stays full until the output unit takes it
+            assert_eq!(dmc.sample_request(), None);
+            dmc.sample_buffer = None;
+        }
+        assert!(!dmc.is_active());
+        assert!(dmc.irq_flag());
+
+        // acknowledged by $4015 writes
+        dmc.set_enabled(false);
+        assert!(!dmc.irq_flag());
+    }
+
+    #[test]
+    fn test_looping_sample_restarts() {
+        let mut dmc = dmc_playing(0xC0, 0x10, 0);
+        assert_eq!(dmc.sample_request(), Some(0xC400));
+        dmc.load_sample(0);
+        dmc.sample_buffer = None;
+        assert_eq!(dmc.sample_request(), Some(0xC400));
+        assert!(!dmc.irq_flag());
+    }
+
+    #[test]
+    fn test_output_unit() {
+        let mut dmc = dmc_playing(0x0F, 0, 0);
+        dmc.write_register(0x4011, 64);
+        dmc.load_sample(0b0000_0111);
+
+        // the first 8 bits are silent, while the empty shift register drains
+        for _ in 0..8 {
+            dmc.clock_output();
+        }
+        assert_eq!(dmc.generate_sample(), 64);
+
+        let mut levels = Vec::new();
+        for _ in 0..8 {
+            dmc.clock_output();
+            levels.push(dmc.generate_sample());
+        }
+        assert_eq!(levels, [66, 68, 70, 68, 66, 64, 62, 60]);
+    }
+}
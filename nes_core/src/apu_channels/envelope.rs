@@ -1,3 +1,5 @@
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
 pub struct Envelope {
     constant_volume: u8,
     loop_envelope: bool,
@@ -40,7 +42,8 @@ impl Envelope {
 
     pub fn write_envelope(&mut self, value: u8) {
         self.loop_envelope = value & 0b0010_0000 != 0;
-        self.use_envelope = value & 0b0001_0000 != 0;
+        // bit 4 selects constant volume
+        self.use_envelope = value & 0b0001_0000 == 0;
         self.constant_volume = value & 0xF;
     }
 
@@ -56,3 +59,24 @@ impl Envelope {
         self.start_flag = true;
     }
 }
+
+impl Snapshot for Envelope {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.constant_volume);
+        writer.write_bool(self.loop_envelope);
+        writer.write_bool(self.use_envelope);
+        writer.write_bool(self.start_flag);
+        writer.write_u8(self.decay_level);
+        writer.write_u8(self.divider);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.constant_volume = reader.read_u8()?;
+        self.loop_envelope = reader.read_bool()?;
+        self.use_envelope = reader.read_bool()?;
+        self.start_flag = reader.read_bool()?;
+        self.decay_level = reader.read_u8()?;
+        self.divider = reader.read_u8()?;
+        Ok(())
+    }
+}
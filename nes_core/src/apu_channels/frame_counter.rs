@@ -0,0 +1,212 @@
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+// Which units a frame counter step clocks. Half frames clock the length
+// counters and sweeps, and always come with a quarter frame, which clocks
+// the envelopes and the triangle's linear counter.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FrameStep {
+    Quarter,
+    Half,
+}
+
+// Step times in CPU cycles (https://www.nesdev.org/wiki/APU_Frame_Counter);
+// the sequencer itself runs at 3728.5, 7456.5, ... APU cycles
+const STEP_1: u32 = 7457;
+const STEP_2: u32 = 14913;
+const STEP_3: u32 = 22371;
+const FOUR_STEP_IRQ: u32 = 29828;
+const FOUR_STEP_4: u32 = 29829;
+const FOUR_STEP_PERIOD: u32 = 29830;
+const FIVE_STEP_5: u32 = 37281;
+const FIVE_STEP_PERIOD: u32 = 37282;
+
+pub struct FrameCounter {
+    cycle: u32,
+    five_step_mode: bool,
+    interrupt_inhibit: bool,
+    irq_flag: bool,
+    // a $4017 write resets the sequencer 3 or 4 CPU cycles later
+    pending_write: Option<(u8, u8)>,
+}
+
+impl Default for FrameCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        Self {
+            cycle: 0,
+            five_step_mode: false,
+            interrupt_inhibit: false,
+            irq_flag: false,
+            pending_write: None,
+        }
+    }
+
+    // `odd_cycle` is the parity of the CPU cycle the write lands on: between
+    // APU cycles the reset takes one cycle longer
+    pub fn write_control(&mut self, value: u8, odd_cycle: bool) {
+        self.interrupt_inhibit = value & 0b0100_0000 != 0;
+        if self.interrupt_inhibit {
+            self.irq_flag = false;
+        }
+        let delay = if odd_cycle { 4 } else { 3 };
+        self.pending_write = Some((value, delay));
+    }
+
+    // every CPU cycle
+    pub fn tick(&mut self) -> Option<FrameStep> {
+        if let Some((value, delay)) = self.pending_write {
+            if delay > 1 {
+                self.pending_write = Some((value, delay - 1));
+            } else {
+                self.pending_write = None;
+                self.five_step_mode = value & 0b1000_0000 != 0;
+                self.cycle = 0;
+                // 5-step mode clocks everything as soon as it is selected
+                if self.five_step_mode {
+                    return Some(FrameStep::Half);
+                }
+                return None;
+            }
+        }
+
+        self.cycle += 1;
+        match (self.five_step_mode, self.cycle) {
+            (_, STEP_1) | (_, STEP_3) => Some(FrameStep::Quarter),
+            (_, STEP_2) => Some(FrameStep::Half),
+            (false, FOUR_STEP_IRQ) => {
+                self.set_irq();
+                None
+            }
+            (false, FOUR_STEP_4) => {
+                self.set_irq();
+                Some(FrameStep::Half)
+            }
+            (false, FOUR_STEP_PERIOD) => {
+                self.set_irq();
+                self.cycle = 0;
+                None
+            }
+            (true, FIVE_STEP_5) => Some(FrameStep::Half),
+            (true, FIVE_STEP_PERIOD) => {
+                self.cycle = 0;
+                None
+            }
+            _ => None,
+        }
+    }
+
+    fn set_irq(&mut self) {
+        if !self.interrupt_inhibit {
+            self.irq_flag = true;
+        }
+    }
+
+    pub fn irq_flag(&self) -> bool {
+        self.irq_flag
+    }
+
+    // on $4015 reads
+    pub fn clear_irq(&mut self) {
+        self.irq_flag = false;
+    }
+}
+
+impl Snapshot for FrameCounter {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.cycle);
+        writer.write_bool(self.five_step_mode);
+        writer.write_bool(self.interrupt_inhibit);
+        writer.write_bool(self.irq_flag);
+        let (value, delay) = self.pending_write.unwrap_or((0, 0));
+        writer.write_u8(value);
+        writer.write_u8(delay);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.cycle = reader.read_u32()? % FIVE_STEP_PERIOD;
+        self.five_step_mode = reader.read_bool()?;
+        self.interrupt_inhibit = reader.read_bool()?;
+        self.irq_flag = reader.read_bool()?;
+        let value = reader.read_u8()?;
+        let delay = reader.read_u8()?;
+        self.pending_write = if delay > 0 {
+            Some((value, delay.min(4)))
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // (cycle, step) for every step over `cycles` CPU cycles
+    fn steps(frame_counter: &mut FrameCounter, cycles: u32) -> Vec<(u32, FrameStep)> {
+        (1..=cycles)
+            .filter_map(|cycle| frame_counter.tick().map(|step| (cycle, step)))
+            .collect()
+    }
+
+    #[test]
+    fn test_four_step_sequence() {
+        let mut frame_counter = FrameCounter::new();
+        assert_eq!(
+            steps(&mut frame_counter, FOUR_STEP_PERIOD + STEP_1),
+            [
+                (7457, FrameStep::Quarter),
+                (14913, FrameStep::Half),
+                (22371, FrameStep::Quarter),
+                (29829, FrameStep::Half),
+                (29830 + 7457, FrameStep::Quarter),
+            ]
+        );
+        assert!(frame_counter.irq_flag());
+        frame_counter.clear_irq();
+
+        // no IRQ while inhibited, and the write clears a pending one
+        let mut frame_counter = FrameCounter::new();
+        steps(&mut frame_counter, FOUR_STEP_PERIOD);
+        assert!(frame_counter.irq_flag());
+        frame_counter.write_control(0b0100_0000, false);
+        assert!(!frame_counter.irq_flag());
+        steps(&mut frame_counter, FOUR_STEP_PERIOD);
+        assert!(!frame_counter.irq_flag());
+    }
+
+    #[test]
+    fn test_five_step_sequence() {
+        let mut frame_counter = FrameCounter::new();
+        frame_counter.write_control(0b1000_0000, false);
+        assert_eq!(
+            steps(&mut frame_counter, 3 + FIVE_STEP_PERIOD),
+            [
+                // clocked as soon as the write takes effect
+                (3, FrameStep::Half),
+                (3 + 7457, FrameStep::Quarter),
+                (3 + 14913, FrameStep::Half),
+                (3 + 22371, FrameStep::Quarter),
+                (3 + 37281, FrameStep::Half),
+            ]
+        );
+        assert!(!frame_counter.irq_flag());
+    }
+
+    #[test]
+    fn test_write_resets_the_sequencer_after_a_delay() {
+        let mut frame_counter = FrameCounter::new();
+        steps(&mut frame_counter, 7000);
+        frame_counter.write_control(0, true);
+        // the old sequence would have clocked at 7457
+        assert_eq!(
+            steps(&mut frame_counter, 4 + STEP_1),
+            [(4 + 7457, FrameStep::Quarter)]
+        );
+    }
+}
@@ -1,6 +1,8 @@
+pub mod dmc_channel;
 pub mod envelope;
 pub mod frame_counter;
 pub mod length_counter;
+pub mod noise_channel;
 pub mod pulse_channel;
 pub mod sweep_unit;
 pub mod triangle_channel;
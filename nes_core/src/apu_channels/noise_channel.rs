@@ -0,0 +1,199 @@
+use super::{envelope::Envelope, length_counter::LengthCounter};
+use crate::{
+    rom::Timing,
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+// timer periods in CPU cycles
+const NTSC_PERIODS: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+const PAL_PERIODS: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+pub struct NoiseChannel {
+    enabled: bool,
+
+    length_counter: LengthCounter,
+    envelope: Envelope,
+
+    periods: &'static [u16; 16],
+    // taps bit 6 instead of bit 1, for a 93 or 31 step sequence
+    short_mode: bool,
+    shift_register: u16,
+    timer_load: u16,
+    timer: u16,
+}
+
+impl NoiseChannel {
+    pub fn new(timing: Timing) -> Self {
+        Self {
+            enabled: false,
+            length_counter: LengthCounter::new(),
+            envelope: Envelope::new(),
+            periods: match timing {
+                Timing::PAL => &PAL_PERIODS,
+                _ => &NTSC_PERIODS,
+            },
+            short_mode: false,
+            shift_register: 1,
+            timer_load: NTSC_PERIODS[0] - 1,
+            timer: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x400C => {
+                self.envelope.write_envelope(data);
+                self.length_counter.set_halt(data & 0b0010_0000 != 0);
+            }
+            0x400E => {
+                self.short_mode = data & 0b1000_0000 != 0;
+                self.timer_load = self.periods[(data & 0b1111) as usize] - 1;
+            }
+            0x400F => {
+                if self.enabled {
+                    self.length_counter.set(data);
+                }
+                self.envelope.set_start_flag();
+            }
+            _ => {}
+        }
+    }
+
+    // every CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_load;
+            self.clock_shift_register();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_shift_register(&mut self) {
+        let tap = if self.short_mode { 6 } else { 1 };
+        let feedback = (self.shift_register ^ (self.shift_register >> tap)) & 1;
+        self.shift_register = (self.shift_register >> 1) | (feedback << 14);
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter.disable();
+        }
+    }
+
+    pub fn is_length_non_zero(&self) -> bool {
+        self.length_counter.is_non_zero()
+    }
+
+    // 0..=15
+    pub fn generate_sample(&self) -> u8 {
+        if self.shift_register & 1 == 0 && self.length_counter.is_non_zero() {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+}
+
+impl Snapshot for NoiseChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        self.length_counter.save_state(writer);
+        self.envelope.save_state(writer);
+        writer.write_bool(self.short_mode);
+        writer.write_u16(self.shift_register);
+        writer.write_u16(self.timer_load);
+        writer.write_u16(self.timer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = reader.read_bool()?;
+        self.length_counter.load_state(reader)?;
+        self.envelope.load_state(reader)?;
+        self.short_mode = reader.read_bool()?;
+        self.shift_register = reader.read_u16()? & 0x7FFF;
+        self.timer_load = reader.read_u16()?;
+        self.timer = reader.read_u16()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sequence_length(noise: &mut NoiseChannel) -> usize {
+        let start = noise.shift_register;
+        for steps in 1..=32767 {
+            noise.clock_shift_register();
+            if noise.shift_register == start {
+                return steps;
+            }
+        }
+        panic!("the shift register never came back to {:#x}", start);
+    }
+
+    #[test]
+    fn test_lfsr_modes() {
+        let mut noise = NoiseChannel::new(Timing::NTSC);
+        noise.clock_shift_register();
+        assert_eq!(noise.shift_register, 0x4000);
+        assert_eq!(sequence_length(&mut noise), 32767);
+
+        noise.shift_register = 1;
+        noise.write_register(0x400E, 0b1000_0000);
+        assert_eq!(sequence_length(&mut noise), 93);
+    }
+
+    #[test]
+    fn test_period_tables() {
+        let mut ntsc = NoiseChannel::new(Timing::NTSC);
+        let mut pal = NoiseChannel::new(Timing::PAL);
+        ntsc.write_register(0x400E, 0x0F);
+        pal.write_register(0x400E, 0x0F);
+
+        // the first clock reloads the timer
+        ntsc.clock_timer();
+        pal.clock_timer();
+        let clocks_per_step = |noise: &mut NoiseChannel| {
+            let start = noise.shift_register;
+            let mut clocks = 0;
+            while noise.shift_register == start {
+                noise.clock_timer();
+                clocks += 1;
+            }
+            clocks
+        };
+        assert_eq!(clocks_per_step(&mut ntsc), 4068);
+        assert_eq!(clocks_per_step(&mut pal), 3778);
+    }
+
+    #[test]
+    fn test_output() {
+        let mut noise = NoiseChannel::new(Timing::NTSC);
+        noise.set_enabled(true);
+        noise.write_register(0x400C, 0b0001_0111);
+        noise.write_register(0x400F, 0b0000_1000);
+        assert_eq!(noise.shift_register & 1, 1);
+        assert_eq!(noise.generate_sample(), 0);
+
+        noise.clock_shift_register();
+        assert_eq!(noise.generate_sample(), 7);
+
+        noise.set_enabled(false);
+        assert_eq!(noise.generate_sample(), 0);
+    }
+}
@@ -0,0 +1,197 @@
+use super::{
+    envelope::Envelope,
+    length_counter::LengthCounter,
+    sweep_unit::{Negate, SweepUnit},
+};
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+const EIGHTH_DUTY_CYCLE: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+const QUARTER_DUTY_CYCLE: [u8; 8] = [0, 0, 0, 0, 0, 0, 1, 1];
+const HALF_DUTY_CYCLE: [u8; 8] = [0, 0, 0, 0, 1, 1, 1, 1];
+const NEGATIVE_QUARTER_DUTY_CYCLE: [u8; 8] = [1, 1, 1, 1, 1, 1, 0, 0];
+
+pub struct PulseChannel {
+    enabled: bool,
+
+    length_counter: LengthCounter,
+    envelope: Envelope,
+    sweep_unit: SweepUnit,
+
+    duty_cycle: [u8; 8],
+
+    sequence: usize,
+    timer_load: u16,
+    timer: u16,
+}
+
+impl PulseChannel {
+    pub fn new(negate: Negate) -> Self {
+        Self {
+            enabled: false,
+            length_counter: LengthCounter::new(),
+            envelope: Envelope::new(),
+            sweep_unit: SweepUnit::new(negate),
+            duty_cycle: EIGHTH_DUTY_CYCLE,
+            sequence: 0,
+            timer_load: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr % 4 {
+            0 => {
+                self.duty_cycle = match data >> 6 {
+                    0b00 => EIGHTH_DUTY_CYCLE,
+                    0b01 => QUARTER_DUTY_CYCLE,
+                    0b10 => HALF_DUTY_CYCLE,
+                    0b11 => NEGATIVE_QUARTER_DUTY_CYCLE,
+                    _ => panic!(),
+                };
+                self.envelope.write_envelope(data);
+                self.length_counter.set_halt(data & 0b0010_0000 != 0);
+            }
+            1 => self.sweep_unit.update(data),
+            2 => {
+                self.timer_load = (self.timer_load & 0b0111_0000_0000) | data as u16;
+            }
+            3 => {
+                if self.enabled {
+                    self.length_counter.set(data);
+                }
+                self.timer_load = (self.timer_load & 0b1111_1111) | ((data as u16 & 0b111) << 8);
+
+                self.sequence = 0;
+                self.envelope.set_start_flag();
+            }
+            _ => {}
+        }
+    }
+
+    // 0..=15
+    pub fn generate_sample(&self) -> u8 {
+        if self.duty_cycle[self.sequence] != 0
+            && self.length_counter.is_non_zero()
+            && !self.sweep_unit.is_muting(self.timer_load)
+        {
+            self.envelope.volume()
+        } else {
+            0
+        }
+    }
+
+    // every other CPU cycle
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_load;
+            self.sequence = (self.sequence + 1) & 7;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+        self.sweep_unit.clock(&mut self.timer_load);
+    }
+
+    pub fn is_length_non_zero(&self) -> bool {
+        self.length_counter.is_non_zero()
+    }
+
+    pub fn set_enabled(&mut self, value: bool) {
+        self.enabled = value;
+        if !value {
+            self.length_counter.disable();
+        }
+    }
+}
+
+impl Snapshot for PulseChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        self.length_counter.save_state(writer);
+        self.envelope.save_state(writer);
+        self.sweep_unit.save_state(writer);
+        writer.write_bytes(&self.duty_cycle);
+        writer.write_u8(self.sequence as u8);
+        writer.write_u16(self.timer_load);
+        writer.write_u16(self.timer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = reader.read_bool()?;
+        self.length_counter.load_state(reader)?;
+        self.envelope.load_state(reader)?;
+        self.sweep_unit.load_state(reader)?;
+        reader.read_bytes(&mut self.duty_cycle)?;
+        self.sequence = (reader.read_u8()? & 7) as usize;
+        self.timer_load = reader.read_u16()?;
+        self.timer = reader.read_u16()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playing_pulse(duty_and_volume: u8, period: u16) -> PulseChannel {
+        let mut pulse = PulseChannel::new(Negate::OnesComplement);
+        pulse.set_enabled(true);
+        pulse.write_register(0x4000, duty_and_volume);
+        pulse.write_register(0x4002, period as u8);
+        pulse.write_register(0x4003, 0b0000_1000 | (period >> 8) as u8);
+        pulse
+    }
+
+    #[test]
+    fn test_duty_cycle_waveform() {
+        // 25% duty, constant volume 9, period 8
+        let mut pulse = playing_pulse(0b0101_1001, 8);
+        let mut waveform = Vec::new();
+        for _ in 0..8 {
+            waveform.push(pulse.generate_sample());
+            for _ in 0..9 {
+                pulse.clock_timer();
+            }
+        }
+        assert_eq!(waveform, [0, 0, 0, 0, 0, 0, 9, 9]);
+    }
+
+    #[test]
+    fn test_muting() {
+        // period below 8
+        let pulse = playing_pulse(0b1101_1001, 7);
+        assert_eq!(pulse.generate_sample(), 0);
+
+        let mut pulse = playing_pulse(0b1101_1001, 8);
+        assert_eq!(pulse.generate_sample(), 9);
+
+        // length counter expired
+        pulse.write_register(0x4003, 0b0001_1000);
+        for _ in 0..2 {
+            pulse.clock_half_frame();
+        }
+        assert!(!pulse.is_length_non_zero());
+        assert_eq!(pulse.generate_sample(), 0);
+
+        // disabled channels do not load the length counter
+        pulse.set_enabled(false);
+        pulse.write_register(0x4003, 0b0000_1000);
+        assert_eq!(pulse.generate_sample(), 0);
+    }
+
+    #[test]
+    fn test_sweep_adjusts_period_on_half_frames() {
+        let mut pulse = playing_pulse(0b1101_1001, 0x100);
+        // enabled, divider period 0, negate, shift 1
+        pulse.write_register(0x4001, 0b1000_1001);
+        pulse.clock_half_frame();
+        assert_eq!(pulse.timer_load, 0x100 - 0x80 - 1);
+    }
+}
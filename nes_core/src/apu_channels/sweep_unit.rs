@@ -0,0 +1,138 @@
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+// Pulse 1 negates the change with ones' complement (period - change - 1),
+// pulse 2 with two's complement (period - change)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Negate {
+    OnesComplement,
+    TwosComplement,
+}
+
+pub struct SweepUnit {
+    enabled: bool,
+    divider_period: u8,
+    is_negate: bool,
+    shift_count: u8,
+    negate_mode: Negate,
+    divider: u8,
+    reload: bool,
+}
+
+impl SweepUnit {
+    pub fn new(negate_mode: Negate) -> Self {
+        SweepUnit {
+            enabled: false,
+            divider_period: 0,
+            is_negate: false,
+            shift_count: 0,
+            negate_mode,
+            divider: 0,
+            reload: false,
+        }
+    }
+
+    pub fn update(&mut self, value: u8) {
+        self.enabled = value & 0b1000_0000 == 0b1000_0000;
+        self.divider_period = (value & 0b0111_0000) >> 4;
+        self.is_negate = value & 0b0000_1000 == 0b0000_1000;
+        self.shift_count = value & 0b0000_0111;
+        self.reload = true;
+    }
+
+    // computed continuously, whether or not the sweep is enabled
+    pub fn target_period(&self, period: u16) -> u16 {
+        let change = period >> self.shift_count;
+        if !self.is_negate {
+            return period + change;
+        }
+        match self.negate_mode {
+            Negate::OnesComplement => period.saturating_sub(change + 1),
+            Negate::TwosComplement => period.saturating_sub(change),
+        }
+    }
+
+    // the channel is silenced when its period is too small or the sweep
+    // would push it past 11 bits, even with the sweep disabled
+    pub fn is_muting(&self, period: u16) -> bool {
+        period < 8 || self.target_period(period) > 0x7FF
+    }
+
+    // half-frame clock
+    pub fn clock(&mut self, period: &mut u16) {
+        if self.divider == 0 && self.enabled && self.shift_count != 0 && !self.is_muting(*period) {
+            *period = self.target_period(*period);
+        }
+
+        if self.divider == 0 || self.reload {
+            self.divider = self.divider_period;
+            self.reload = false;
+        } else {
+            self.divider -= 1;
+        }
+    }
+}
+
+impl Snapshot for SweepUnit {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        writer.write_u8(self.divider_period);
+        writer.write_bool(self.is_negate);
+        writer.write_u8(self.shift_count);
+        writer.write_u8(self.divider);
+        writer.write_bool(self.reload);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = reader.read_bool()?;
+        self.divider_period = reader.read_u8()?;
+        self.is_negate = reader.read_bool()?;
+        self.shift_count = reader.read_u8()?;
+        self.divider = reader.read_u8()?;
+        self.reload = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_negate_differs_between_channels() {
+        let mut pulse1 = SweepUnit::new(Negate::OnesComplement);
+        let mut pulse2 = SweepUnit::new(Negate::TwosComplement);
+        pulse1.update(0b1000_1001);
+        pulse2.update(0b1000_1001);
+
+        assert_eq!(pulse1.target_period(0x100), 0x100 - 0x80 - 1);
+        assert_eq!(pulse2.target_period(0x100), 0x100 - 0x80);
+    }
+
+    #[test]
+    fn test_muting() {
+        let mut sweep = SweepUnit::new(Negate::TwosComplement);
+        assert!(sweep.is_muting(7));
+        assert!(!sweep.is_muting(8));
+
+        // shift 0 doubles the period: muted from 0x400 up, even disabled
+        sweep.update(0b0000_0000);
+        assert!(!sweep.is_muting(0x3FF));
+        assert!(sweep.is_muting(0x400));
+    }
+
+    #[test]
+    fn test_divider_paces_period_updates() {
+        let mut sweep = SweepUnit::new(Negate::TwosComplement);
+        // enabled, divider period 1, shift 2
+        sweep.update(0b1001_0010);
+        let mut period = 0x100;
+
+        // the divider starts at 0, so the first clock adjusts right away
+        sweep.clock(&mut period);
+        assert_eq!(period, 0x140);
+        sweep.clock(&mut period);
+        assert_eq!(period, 0x140);
+        sweep.clock(&mut period);
+        assert_eq!(period, 0x190);
+    }
+}
@@ -0,0 +1,202 @@
+use super::length_counter::LengthCounter;
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+const TRIANGLE_SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, //
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+pub struct TriangleChannel {
+    enabled: bool,
+    length_counter: LengthCounter,
+    timer: u16,
+    timer_reload: u16,
+    sequence: usize,
+    linear_counter: u8,
+    linear_counter_reload: u8,
+    linear_counter_reload_flag: bool,
+    // also halts the length counter
+    control_flag: bool,
+}
+
+impl TriangleChannel {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            length_counter: LengthCounter::new(),
+            timer: 0,
+            timer_reload: 0,
+            sequence: 0,
+            linear_counter: 0,
+            linear_counter_reload: 0,
+            linear_counter_reload_flag: false,
+            control_flag: false,
+        }
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4008 => {
+                self.control_flag = data & 0b1000_0000 != 0;
+                self.length_counter.set_halt(self.control_flag);
+                self.linear_counter_reload = data & 0b0111_1111;
+            }
+            0x400A => {
+                self.timer_reload = (self.timer_reload & 0xFF00) | data as u16;
+            }
+            0x400B => {
+                self.timer_reload = (self.timer_reload & 0x00FF) | ((data as u16 & 0b111) << 8);
+                if self.enabled {
+                    self.length_counter.set(data);
+                }
+                self.linear_counter_reload_flag = true;
+            }
+            _ => {}
+        }
+    }
+
+    // every CPU cycle; the sequencer only moves while both counters are
+    // non-zero, so a silenced triangle holds its last level
+    pub fn tick_sequencer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_reload;
+            if self.linear_counter > 0 && self.length_counter.is_non_zero() {
+                self.sequence = (self.sequence + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    pub fn clock_quarter_frame(&mut self) {
+        if self.linear_counter_reload_flag {
+            self.linear_counter = self.linear_counter_reload;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control_flag {
+            self.linear_counter_reload_flag = false;
+        }
+    }
+
+    pub fn clock_half_frame(&mut self) {
+        self.length_counter.clock();
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length_counter.disable();
+        }
+    }
+
+    pub fn is_length_non_zero(&self) -> bool {
+        self.length_counter.is_non_zero()
+    }
+
+    // 0..=15
+    pub fn generate_sample(&self) -> u8 {
+        TRIANGLE_SEQUENCE[self.sequence]
+    }
+}
+
+impl Snapshot for TriangleChannel {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.enabled);
+        self.length_counter.save_state(writer);
+        writer.write_u16(self.timer);
+        writer.write_u16(self.timer_reload);
+        writer.write_u8(self.sequence as u8);
+        writer.write_u8(self.linear_counter);
+        writer.write_u8(self.linear_counter_reload);
+        writer.write_bool(self.linear_counter_reload_flag);
+        writer.write_bool(self.control_flag);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.enabled = reader.read_bool()?;
+        self.length_counter.load_state(reader)?;
+        self.timer = reader.read_u16()?;
+        self.timer_reload = reader.read_u16()?;
+        self.sequence = (reader.read_u8()? % 32) as usize;
+        self.linear_counter = reader.read_u8()?;
+        self.linear_counter_reload = reader.read_u8()?;
+        self.linear_counter_reload_flag = reader.read_bool()?;
+        self.control_flag = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn playing_triangle(linear: u8, period: u16) -> TriangleChannel {
+        let mut triangle = TriangleChannel::new();
+        triangle.set_enabled(true);
+        triangle.write_register(0x4008, linear);
+        triangle.write_register(0x400A, period as u8);
+        triangle.write_register(0x400B, 0b0000_1000 | (period >> 8) as u8);
+        triangle.clock_quarter_frame();
+        triangle
+    }
+
+    #[test]
+    fn test_sequencer_steps_through_the_waveform() {
+        let mut triangle = playing_triangle(0x7F, 3);
+        let mut waveform = Vec::new();
+        for _ in 0..32 {
+            for _ in 0..4 {
+                triangle.tick_sequencer();
+            }
+            waveform.push(triangle.generate_sample());
+        }
+        let mut expected = TRIANGLE_SEQUENCE[1..].to_vec();
+        expected.push(TRIANGLE_SEQUENCE[0]);
+        assert_eq!(waveform, expected);
+    }
+
+    #[test]
+    fn test_linear_counter() {
+        let mut triangle = playing_triangle(2, 0);
+        assert_eq!(triangle.linear_counter, 2);
+
+        // without the control flag the reload flag clears after one clock
+        triangle.clock_quarter_frame();
+        triangle.clock_quarter_frame();
+        assert_eq!(triangle.linear_counter, 0);
+
+        // the sequencer stops and holds its level
+        let level = triangle.generate_sample();
+        for _ in 0..10 {
+            triangle.tick_sequencer();
+        }
+        assert_eq!(triangle.generate_sample(), level);
+
+        // with the control flag set it reloads on every clock
+        let mut triangle = playing_triangle(0x80 | 2, 0);
+        for _ in 0..5 {
+            triangle.clock_quarter_frame();
+        }
+        assert_eq!(triangle.linear_counter, 2);
+    }
+
+    #[test]
+    fn test_length_counter() {
+        let mut triangle = playing_triangle(0x7F, 0);
+        triangle.write_register(0x400B, 0b0001_1000);
+        triangle.clock_half_frame();
+        triangle.clock_half_frame();
+        assert!(!triangle.is_length_non_zero());
+
+        // halted by the control flag
+        let mut triangle = playing_triangle(0xFF, 0);
+        triangle.write_register(0x400B, 0b0001_1000);
+        triangle.clock_half_frame();
+        triangle.clock_half_frame();
+        assert!(triangle.is_length_non_zero());
+
+        triangle.set_enabled(false);
+        assert!(!triangle.is_length_non_zero());
+    }
+}
@@ -0,0 +1,424 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    apu::Apu,
+    cpu::Mem,
+    joypad::Joypad,
+    mapper::{self, Mapper},
+    ppu::NesPPU,
+    rom::Rom,
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const RAM: u16 = 0x0000;
+const RAM_MIRRORS_END: u16 = 0x1FFF;
+const PRG_RAM: u16 = 0x6000;
+const PRG_RAM_END: u16 = 0x7FFF;
+const ROM: u16 = 0x8000;
+const ROM_END: u16 = 0xFFFF;
+const PPU_REGISTERS: u16 = 0x2000;
+const PPU_REGISTERS_MIRRORS_END: u16 = 0x3FFF;
+// a DMC fetch usually halts the CPU for 4 cycles, 3 when it lands on a write
+const DMC_FETCH_STALL_CYCLES: u8 = 4;
+const DMC_FETCH_ON_WRITE_STALL_CYCLES: u8 = 3;
+const OAM_DMA_TRANSFER_CYCLES: u16 = 512;
+
+pub struct Bus {
+    cpu_vram: [u8; 2048],
+    mapper: Rc<RefCell<dyn Mapper>>,
+    ppu: NesPPU,
+    apu: Apu,
+
+    cycles: usize,
+    frames: usize,
+    joypad: Joypad,
+    // last value on the CPU data bus, returned by reads nothing answers
+    open_bus: u8,
+    // page written to $4014, copied once the writing instruction completes
+    oam_dma_page: Option<u8>,
+    // cycle of the OAM DMA transfer in progress, if any
+    oam_dma_cycle: Option<u16>,
+    // whether the last CPU bus access was a write
+    last_access_was_write: bool,
+}
+
+impl Bus {
+    pub fn new(rom: Rom) -> Bus {
+        let timing = rom.timing;
+        let mapper = mapper::new_mapper(rom);
+        let ppu = NesPPU::new(mapper.clone());
+
+        Bus {
+            cpu_vram: [0; 2048],
+            mapper,
+            ppu: ppu,
+            cycles: 0,
+            frames: 0,
+            joypad: Joypad::new(),
+            apu: Apu::new(timing),
+            open_bus: 0,
+            oam_dma_page: None,
+            oam_dma_cycle: None,
+            last_access_was_write: false,
+        }
+    }
+
+    pub fn tick(&mut self, cycles: u8) {
+        let mut cycles = cycles;
+        loop {
+            self.cycles += cycles as usize;
+
+            self.apu.tick(cycles);
+
+            if self.ppu.tick(cycles * 3) {
+                self.frames += 1;
+            }
+
+            // the DMC takes over the bus to refill its sample buffer, and the
+            // rest of the machine keeps running while the CPU waits
+            match self.apu.dmc_sample_request() {
+                Some(addr) => {
+                    cycles = self.dmc_stall_cycles();
+                    let data = self.mem_read(addr);
+                    self.apu.load_dmc_sample(data);
+                }
+                None => break,
+            }
+        }
+    }
+
+    // The CPU only ticks the bus once per instruction, so a fetch is taken to
+    // land on the last access of the instruction that triggered it. During
+    // OAM DMA the DMC steals a cycle or two from the transfer instead.
+    fn dmc_stall_cycles(&self) -> u8 {
+        match self.oam_dma_cycle {
+            Some(cycle) if cycle == OAM_DMA_TRANSFER_CYCLES - 2 => 1,
+            Some(cycle) if cycle == OAM_DMA_TRANSFER_CYCLES - 1 => 3,
+            Some(_) => 2,
+            None if self.last_access_was_write => DMC_FETCH_ON_WRITE_STALL_CYCLES,
+            None => DMC_FETCH_STALL_CYCLES,
+        }
+    }
+
+    // OAM DMA halts the CPU for 513 cycles, plus one when it starts on an
+    // odd cycle, while the PPU and APU keep running: an alignment cycle,
+    // then 256 pairs of reading the page and writing it to $2004.
+    pub fn run_oam_dma(&mut self) {
+        if let Some(page) = self.oam_dma_page.take() {
+            self.tick(if self.cycles % 2 == 1 { 2 } else { 1 });
+
+            let hi = (page as u16) << 8;
+            for i in 0..256u16 {
+                let data = self.mem_read(hi | i);
+                self.oam_dma_cycle = Some(i * 2);
+                self.tick(1);
+                self.ppu.write_register(0x2004, data);
+                self.oam_dma_cycle = Some(i * 2 + 1);
+                self.tick(1);
+            }
+            self.oam_dma_cycle = None;
+        }
+    }
+
+    pub fn mapper(&self) -> Rc<RefCell<dyn Mapper>> {
+        self.mapper.clone()
+    }
+
+    pub fn ppu(&self) -> &NesPPU {
+        &self.ppu
+    }
+
+    pub fn joypad(&mut self) -> &mut Joypad {
+        &mut self.joypad
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.cpu_vram
+    }
+
+    // Reads memory without any side effects, for tools watching the machine.
+    // Only RAM, PRG-RAM and PRG-ROM can be peeked: reading the I/O registers
+    // changes their state, so those return None.
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            RAM..=RAM_MIRRORS_END => Some(self.cpu_vram[(addr & 0b111_1111_1111) as usize]),
+            PRG_RAM..=PRG_RAM_END => Some(self.mapper.borrow().read_prg_ram(addr)),
+            ROM..=ROM_END => Some(self.mapper.borrow().read_prg(addr)),
+            _ => None,
+        }
+    }
+
+    // number of frames the PPU has finished drawing since power on; counts
+    // every frame, whether or not the game enabled the vblank NMI
+    pub fn frame_count(&self) -> usize {
+        self.frames
+    }
+
+    pub fn poll_nmi_status(&mut self) -> Option<u8> {
+        self.ppu.nmi_interrupt.take()
+    }
+
+    pub fn poll_irq_status(&mut self) -> bool {
+        // the IRQ line is shared: any source pulling it low keeps it asserted
+        // until that source is acknowledged
+        let apu_irq = self.apu.irq_pending();
+        let mapper_irq = self.mapper.borrow().irq_pending();
+        apu_irq || mapper_irq
+    }
+
+    pub fn get_audio_samples(&mut self) -> Vec<i16> {
+        self.apu.take_samples()
+    }
+
+    fn load_components(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        reader.read_bytes(&mut self.cpu_vram)?;
+        self.cycles = reader.read_u64()? as usize;
+        self.open_bus = reader.read_u8()?;
+        self.frames = reader.read_u64()? as usize;
+        self.last_access_was_write = reader.read_bool()?;
+        self.ppu.load_state(reader)?;
+        self.apu.load_state(reader)?;
+        self.joypad.load_state(reader)?;
+        self.mapper.borrow_mut().load_state(reader)
+    }
+}
+
+impl Mem for Bus {
+    fn mem_read(&mut self, addr: u16) -> u8 {
+        let data = match addr {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b00000111_11111111;
+                self.cpu_vram[mirror_down_addr as usize]
+            }
+
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.ppu.read_register(addr),
+
+            PRG_RAM..=PRG_RAM_END => self.mapper.borrow().read_prg_ram(addr),
+
+            ROM..=ROM_END => self.mapper.borrow().read_prg(addr),
+
+            0x4015 => self.apu.read_register() | (self.open_bus & 0x20),
+
+            // the controller ports only drive the low bits
+            0x4016 => self.joypad.read() | (self.open_bus & 0xE0),
+
+            0x4017 => {
+                // ignore joypad 2
+                self.open_bus & 0xE0
+            }
+
+            _ => self.open_bus,
+        };
+        self.open_bus = data;
+        self.last_access_was_write = false;
+        data
+    }
+
+    fn mem_write(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        self.last_access_was_write = true;
+        match addr {
+            RAM..=RAM_MIRRORS_END => {
+                let mirror_down_addr = addr & 0b11111111111;
+                self.cpu_vram[mirror_down_addr as usize] = data;
+            }
+
+            PPU_REGISTERS..=PPU_REGISTERS_MIRRORS_END => self.ppu.write_register(addr, data),
+
+            0x4000..=0x4013 | 0x4015 | 0x4017 => {
+                self.apu.write_register(addr, data, self.cycles);
+            }
+
+            0x4014 => self.oam_dma_page = Some(data),
+
+            0x4016 => self.joypad.write(data),
+
+            PRG_RAM..=PRG_RAM_END => self.mapper.borrow_mut().write_prg_ram(addr, data),
+
+            ROM..=ROM_END => self.mapper.borrow_mut().write_prg(addr, data),
+
+            _ => {}
+        }
+    }
+}
+
+impl Snapshot for Bus {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.cpu_vram);
+        writer.write_u64(self.cycles as u64);
+        writer.write_u8(self.open_bus);
+        writer.write_u64(self.frames as u64);
+        writer.write_bool(self.last_access_was_write);
+        self.ppu.save_state(writer);
+        self.apu.save_state(writer);
+        self.joypad.save_state(writer);
+        self.mapper.borrow().save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        // components load as they parse, so keep a copy to restore if the state is bad
+        let mut previous = StateWriter::new();
+        self.save_state(&mut previous);
+
+        let result = self.load_components(reader);
+        if result.is_err() {
+            let previous = previous.into_bytes();
+            let mut reader = StateReader::new(&previous).expect("state we just wrote");
+            self.load_components(&mut reader)
+                .expect("state we just wrote");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::{test, Mirroring};
+
+    #[test]
+    fn test_unmapped_reads_return_open_bus() {
+        let mut bus = Bus::new(test::test_rom());
+        bus.mem_write(0x0010, 0x44);
+        assert_eq!(bus.mem_read(0x5000), 0x44);
+
+        bus.mem_read(0x0010);
+        assert_eq!(bus.mem_read(0x4014), 0x44);
+        assert_eq!(bus.mem_read(0x4017), 0x40);
+    }
+
+    #[test]
+    fn test_peek_has_no_side_effects() {
+        let mut bus = Bus::new(test::test_rom_with(
+            vec![1; 0x8000],
+            vec![],
+            0,
+            Mirroring::HORIZONTAL,
+        ));
+        bus.mem_write(0x0810, 0x44);
+        bus.mem_write(0x6000, 0x55);
+        bus.mem_write(0x0000, 0x66);
+
+        assert_eq!(bus.peek(0x0010), Some(0x44));
+        assert_eq!(bus.peek(0x6000), Some(0x55));
+        assert_eq!(bus.peek(0x8000), Some(1));
+        assert_eq!(bus.peek(0x2002), None);
+        assert_eq!(bus.peek(0x4016), None);
+        // open bus still holds the last value written
+        assert_eq!(bus.mem_read(0x5000), 0x66);
+    }
+
+    #[test]
+    fn test_oam_dma_copies_page_and_stalls() {
+        for start_cycles in [0, 1] {
+            let mut bus = Bus::new(test::test_rom());
+            bus.tick(start_cycles);
+            for i in 0..256u16 {
+                bus.mem_write(0x0200 + i, i as u8);
+            }
+            bus.mem_write(0x2003, 0x10);
+
+            bus.mem_write(0x4014, 0x02);
+            assert_eq!(bus.ppu().oam_data[0x10], 0);
+            bus.run_oam_dma();
+
+            let stall = if start_cycles == 1 { 514 } else { 513 };
+            assert_eq!(bus.cycles, start_cycles as usize + stall);
+            // the copy starts at OAMADDR and wraps around
+            assert_eq!(bus.ppu().oam_data[0x10], 0x00);
+            assert_eq!(bus.ppu().oam_data[0x0F], 0xFF);
+            assert_eq!(bus.ppu().oam_addr, 0x10);
+
+            // nothing left to copy
+            bus.run_oam_dma();
+            assert_eq!(bus.cycles, start_cycles as usize + stall);
+        }
+    }
+
+    #[test]
+    fn test_dmc_fetch_stalls_the_cpu() {
+        let mut bus = Bus::new(test::test_rom());
+        // a 1 byte sample at $C000
+        bus.mem_write(0x4010, 0x0F);
+        bus.mem_write(0x4012, 0x00);
+        bus.mem_write(0x4013, 0x00);
+        bus.mem_write(0x4015, 0x10);
+        // the instruction ends on a read
+        bus.mem_read(0x0000);
+
+        bus.tick(1);
+        assert_eq!(bus.cycles, 1 + DMC_FETCH_STALL_CYCLES as usize);
+        // the fetched byte was left on the data bus
+        let fetched = bus.open_bus;
+        assert_eq!(fetched, bus.mem_read(0xC000));
+
+        // nothing more to fetch
+        bus.tick(1);
+        assert_eq!(bus.cycles, 2 + DMC_FETCH_STALL_CYCLES as usize);
+    }
+
+    #[test]
+    fn test_dmc_fetch_on_a_write_stalls_less() {
+        let mut bus = Bus::new(test::test_rom());
+        bus.mem_write(0x4010, 0x0F);
+        bus.mem_write(0x4013, 0x00);
+        bus.mem_write(0x4015, 0x10);
+
+        bus.tick(1);
+        assert_eq!(bus.cycles, 1 + DMC_FETCH_ON_WRITE_STALL_CYCLES as usize);
+    }
+
+    #[test]
+    fn test_dmc_fetch_during_oam_dma_steals_fewer_cycles() {
+        let mut bus = Bus::new(test::test_rom());
+        bus.oam_dma_cycle = Some(100);
+        assert_eq!(bus.dmc_stall_cycles(), 2);
+        bus.oam_dma_cycle = Some(OAM_DMA_TRANSFER_CYCLES - 2);
+        assert_eq!(bus.dmc_stall_cycles(), 1);
+        bus.oam_dma_cycle = Some(OAM_DMA_TRANSFER_CYCLES - 1);
+        assert_eq!(bus.dmc_stall_cycles(), 3);
+
+        bus.mem_write(0x4014, 0x00);
+        bus.run_oam_dma();
+        assert_eq!(bus.oam_dma_cycle, None);
+    }
+
+    #[test]
+    fn test_apu_irqs_reach_the_cpu() {
+        let mut bus = Bus::new(test::test_rom());
+        assert!(!bus.poll_irq_status());
+
+        // the frame IRQ, acknowledged by reading $4015
+        for _ in 0..29830 / 10 {
+            bus.tick(10);
+        }
+        assert!(bus.poll_irq_status());
+        assert_eq!(bus.mem_read(0x4015) & 0x40, 0x40);
+        assert!(!bus.poll_irq_status());
+
+        // the DMC IRQ at the end of a sample, acknowledged by writing $4015
+        bus.mem_write(0x4017, 0x40);
+        bus.mem_write(0x4010, 0x8F);
+        bus.mem_write(0x4013, 0x00);
+        bus.mem_write(0x4015, 0x10);
+        bus.tick(1);
+        assert!(bus.poll_irq_status());
+        assert_eq!(bus.mem_read(0x4015) & 0x80, 0x80);
+        assert!(bus.poll_irq_status());
+        bus.mem_write(0x4015, 0x00);
+        assert!(!bus.poll_irq_status());
+    }
+
+    #[test]
+    fn test_odd_writes_do_not_panic() {
+        let mut bus = Bus::new(test::test_rom());
+        let rom_byte = bus.mem_read(0x8000);
+        bus.mem_write(0x8000, rom_byte.wrapping_add(1));
+        assert_eq!(bus.mem_read(0x8000), rom_byte);
+
+        bus.mem_write(0x2002, 0x12);
+        bus.mem_write(0x3FFA, 0x34);
+        assert_eq!(bus.mem_read(0x2000), 0x34);
+        assert_eq!(bus.mem_read(0x3FF9), 0x34);
+    }
+}
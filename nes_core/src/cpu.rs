@@ -4,6 +4,7 @@ use crate::{
     bus::Bus,
     interrupt::{self, Interrupt},
     opcode::{self},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
 };
 
 const STACK: u16 = 0x0100;
@@ -25,14 +26,14 @@ pub enum AddressingMode {
     Indirect_Y,
     NoneAddressing,
 }
-pub struct CPU<'a> {
+pub struct CPU {
     pub register_a: u8,
     pub register_x: u8,
     pub register_y: u8,
     pub status: u8,
     pub program_counter: u16,
     pub stack_pointer: u8,
-    pub bus: Bus<'a>,
+    pub bus: Bus,
 }
 
 pub trait Mem {
@@ -54,8 +55,8 @@ pub trait Mem {
     }
 }
 
-impl<'a> CPU<'a> {
-    pub fn new(bus: Bus<'a>) -> Self {
+impl CPU {
+    pub fn new(bus: Bus) -> Self {
         CPU {
             register_a: 0,
             register_x: 0,
@@ -766,6 +767,38 @@ impl<'a> CPU<'a> {
         self.run_with_callback(|_| {});
     }
 
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.write_u8(self.register_a);
+        writer.write_u8(self.register_x);
+        writer.write_u8(self.register_y);
+        writer.write_u8(self.status);
+        writer.write_u16(self.program_counter);
+        writer.write_u8(self.stack_pointer);
+        self.bus.save_state(&mut writer);
+        writer.into_bytes()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = StateReader::new(data)?;
+        let register_a = reader.read_u8()?;
+        let register_x = reader.read_u8()?;
+        let register_y = reader.read_u8()?;
+        let status = reader.read_u8()?;
+        let program_counter = reader.read_u16()?;
+        let stack_pointer = reader.read_u8()?;
+        // the bus rolls itself back on error, so only commit the registers after it
+        self.bus.load_state(&mut reader)?;
+
+        self.register_a = register_a;
+        self.register_x = register_x;
+        self.register_y = register_y;
+        self.status = status;
+        self.program_counter = program_counter;
+        self.stack_pointer = stack_pointer;
+        Ok(())
+    }
+
     fn interrupt(&mut self, interrupt: Interrupt) {
         self.stack_push_u16(self.program_counter);
         self.status &= 0b1110_1111;
@@ -782,354 +815,383 @@ impl<'a> CPU<'a> {
     where
         F: FnMut(&mut CPU),
     {
-        let ref opcodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
+        self.run_until(|cpu| {
+            callback(cpu);
+            false
+        });
+    }
 
+    // like run_with_callback, but returns as soon as the callback returns true
+    pub fn run_until<F>(&mut self, mut callback: F)
+    where
+        F: FnMut(&mut CPU) -> bool,
+    {
         loop {
-            if let Some(_nmi) = self.bus.poll_nmi_status() {
-                self.interrupt(interrupt::NMI);
-            } else if self.bus.poll_irq_status() && (self.status & 0b0000_0100 == 0) {
-                println!("triggered IRQ interrupt");
-                self.interrupt(interrupt::IRQ);
+            self.poll_interrupts();
+
+            if callback(self) {
+                return;
             }
 
-            callback(self);
+            if !self.execute_next() {
+                return;
+            }
+        }
+    }
 
-            let opscode = self.mem_read(self.program_counter);
-            self.program_counter += 1;
-            let program_counter_state = self.program_counter;
+    // services a pending interrupt, then runs one instruction; returns false
+    // when the CPU hit BRK and stopped
+    pub fn step(&mut self) -> bool {
+        self.poll_interrupts();
+        self.execute_next()
+    }
 
-            let instruction = opcodes
-                .get(&opscode)
-                .expect(&format!("OpCode {:x} is not recognized", opscode));
+    fn poll_interrupts(&mut self) {
+        if let Some(_nmi) = self.bus.poll_nmi_status() {
+            self.interrupt(interrupt::NMI);
+        } else if self.bus.poll_irq_status() && (self.status & 0b0000_0100 == 0) {
+            self.interrupt(interrupt::IRQ);
+        }
+    }
 
-            /*println!(
-                "Instruction: {}, OpCode: {:#04x}, CPU Status: {:08b}, PC: {}",
-                instruction.mnemonic, instruction.op_code, self.status, self.program_counter
-            );*/
+    fn execute_next(&mut self) -> bool {
+        let ref opcodes: HashMap<u8, &'static opcode::OpCode> = *opcode::OPCODES_MAP;
 
-            match instruction.mnemonic {
-                "ADC" => {
-                    self.adc(&instruction.addr);
-                }
+        let opscode = self.mem_read(self.program_counter);
+        self.program_counter += 1;
+        let program_counter_state = self.program_counter;
 
-                "AND" => {
-                    self.and(&instruction.addr);
-                }
+        let instruction = opcodes
+            .get(&opscode)
+            .expect(&format!("OpCode {:x} is not recognized", opscode));
 
-                "ASL" => {
-                    if instruction.op_code == 0x0A {
-                        self.asl_accumulator();
-                    } else {
-                        self.asl(&instruction.addr);
-                    }
-                }
+        /*println!(
+            "Instruction: {}, OpCode: {:#04x}, CPU Status: {:08b}, PC: {}",
+            instruction.mnemonic, instruction.op_code, self.status, self.program_counter
+        );*/
 
-                "BCC" => {
-                    self.bcc();
-                }
+        match instruction.mnemonic {
+            "ADC" => {
+                self.adc(&instruction.addr);
+            }
 
-                "BCS" => {
-                    self.bcs();
-                }
+            "AND" => {
+                self.and(&instruction.addr);
+            }
 
-                "BEQ" => {
-                    self.beq();
+            "ASL" => {
+                if instruction.op_code == 0x0A {
+                    self.asl_accumulator();
+                } else {
+                    self.asl(&instruction.addr);
                 }
+            }
 
-                "BIT" => {
-                    self.bit(&instruction.addr);
-                }
+            "BCC" => {
+                self.bcc();
+            }
 
-                "BMI" => {
-                    self.bmi();
-                }
+            "BCS" => {
+                self.bcs();
+            }
 
-                "BNE" => {
-                    self.bne();
-                }
+            "BEQ" => {
+                self.beq();
+            }
 
-                "BPL" => {
-                    self.bpl();
-                }
+            "BIT" => {
+                self.bit(&instruction.addr);
+            }
 
-                "BVC" => {
-                    self.bvc();
-                }
+            "BMI" => {
+                self.bmi();
+            }
 
-                "BVS" => {
-                    self.bvs();
-                }
+            "BNE" => {
+                self.bne();
+            }
 
-                "CLC" => {
-                    self.clc();
-                }
+            "BPL" => {
+                self.bpl();
+            }
 
-                "CLD" => {
-                    self.cld();
-                }
+            "BVC" => {
+                self.bvc();
+            }
 
-                "CLI" => {
-                    self.cli();
-                }
+            "BVS" => {
+                self.bvs();
+            }
 
-                "CLV" => {
-                    self.clv();
-                }
+            "CLC" => {
+                self.clc();
+            }
 
-                "CMP" => {
-                    self.cmp(&instruction.addr);
-                }
+            "CLD" => {
+                self.cld();
+            }
 
-                "CPX" => {
-                    self.cpx(&instruction.addr);
-                }
+            "CLI" => {
+                self.cli();
+            }
 
-                "CPY" => {
-                    self.cpy(&instruction.addr);
-                }
+            "CLV" => {
+                self.clv();
+            }
 
-                "DEC" => {
-                    self.dec(&instruction.addr);
-                }
+            "CMP" => {
+                self.cmp(&instruction.addr);
+            }
 
-                "DEX" => {
-                    self.dex();
-                }
+            "CPX" => {
+                self.cpx(&instruction.addr);
+            }
 
-                "DEY" => {
-                    self.dey();
-                }
+            "CPY" => {
+                self.cpy(&instruction.addr);
+            }
 
-                "EOR" => {
-                    self.eor(&instruction.addr);
-                }
+            "DEC" => {
+                self.dec(&instruction.addr);
+            }
 
-                "INC" => {
-                    self.inc(&instruction.addr);
-                }
+            "DEX" => {
+                self.dex();
+            }
 
-                "INX" => {
-                    self.inx();
-                }
+            "DEY" => {
+                self.dey();
+            }
 
-                "INY" => {
-                    self.iny();
-                }
+            "EOR" => {
+                self.eor(&instruction.addr);
+            }
 
-                "JMP" => {
-                    if instruction.op_code == 0x4C {
-                        self.jmp_abs();
-                    } else {
-                        self.jmp_indirect();
-                    }
-                }
+            "INC" => {
+                self.inc(&instruction.addr);
+            }
 
-                "JSR" => {
-                    self.jsr();
-                }
+            "INX" => {
+                self.inx();
+            }
 
-                "LDA" => {
-                    self.lda(&instruction.addr);
-                }
+            "INY" => {
+                self.iny();
+            }
 
-                "LDX" => {
-                    self.ldx(&instruction.addr);
+            "JMP" => {
+                if instruction.op_code == 0x4C {
+                    self.jmp_abs();
+                } else {
+                    self.jmp_indirect();
                 }
+            }
 
-                "LDY" => {
-                    self.ldy(&instruction.addr);
-                }
+            "JSR" => {
+                self.jsr();
+            }
 
-                "LSR" => {
-                    if instruction.op_code == 0x4A {
-                        self.lsr_accumulator();
-                    } else {
-                        self.lsr(&instruction.addr);
-                    }
-                }
+            "LDA" => {
+                self.lda(&instruction.addr);
+            }
+
+            "LDX" => {
+                self.ldx(&instruction.addr);
+            }
 
-                "NOP" => {}
+            "LDY" => {
+                self.ldy(&instruction.addr);
+            }
 
-                "ORA" => {
-                    self.ora(&instruction.addr);
+            "LSR" => {
+                if instruction.op_code == 0x4A {
+                    self.lsr_accumulator();
+                } else {
+                    self.lsr(&instruction.addr);
                 }
+            }
 
-                "PHA" => {
-                    self.pha();
-                }
+            "NOP" => {}
 
-                "PHP" => {
-                    self.php();
-                }
+            "ORA" => {
+                self.ora(&instruction.addr);
+            }
 
-                "PLA" => {
-                    self.pla();
-                }
+            "PHA" => {
+                self.pha();
+            }
 
-                "PLP" => {
-                    self.plp();
-                }
+            "PHP" => {
+                self.php();
+            }
 
-                "ROL" => {
-                    if instruction.op_code == 0x2A {
-                        self.rol_accumulator();
-                    } else {
-                        self.rol(&instruction.addr);
-                    }
-                }
+            "PLA" => {
+                self.pla();
+            }
 
-                "ROR" => {
-                    if instruction.op_code == 0x6A {
-                        self.ror_accumulator();
-                    } else {
-                        self.ror(&instruction.addr);
-                    }
-                }
+            "PLP" => {
+                self.plp();
+            }
 
-                "RTI" => {
-                    self.rti();
+            "ROL" => {
+                if instruction.op_code == 0x2A {
+                    self.rol_accumulator();
+                } else {
+                    self.rol(&instruction.addr);
                 }
+            }
 
-                "RTS" => {
-                    self.rts();
+            "ROR" => {
+                if instruction.op_code == 0x6A {
+                    self.ror_accumulator();
+                } else {
+                    self.ror(&instruction.addr);
                 }
+            }
 
-                "SBC" => {
-                    self.sbc(&instruction.addr);
-                }
+            "RTI" => {
+                self.rti();
+            }
 
-                "SEC" => {
-                    self.sec();
-                }
+            "RTS" => {
+                self.rts();
+            }
 
-                "SED" => {
-                    self.sed();
-                }
+            "SBC" => {
+                self.sbc(&instruction.addr);
+            }
 
-                "SEI" => {
-                    self.sei();
-                }
+            "SEC" => {
+                self.sec();
+            }
 
-                "STA" => {
-                    self.sta(&instruction.addr);
-                }
+            "SED" => {
+                self.sed();
+            }
 
-                "STX" => {
-                    self.stx(&instruction.addr);
-                }
+            "SEI" => {
+                self.sei();
+            }
 
-                "STY" => {
-                    self.sty(&instruction.addr);
-                }
+            "STA" => {
+                self.sta(&instruction.addr);
+            }
 
-                "TAX" => {
-                    self.tax();
-                }
+            "STX" => {
+                self.stx(&instruction.addr);
+            }
 
-                "TAY" => {
-                    self.tay();
-                }
+            "STY" => {
+                self.sty(&instruction.addr);
+            }
 
-                "TSX" => {
-                    self.tsx();
-                }
+            "TAX" => {
+                self.tax();
+            }
 
-                "TXA" => {
-                    self.txa();
-                }
+            "TAY" => {
+                self.tay();
+            }
 
-                "TXS" => {
-                    self.txs();
-                }
+            "TSX" => {
+                self.tsx();
+            }
 
-                "TYA" => {
-                    self.tya();
-                }
+            "TXA" => {
+                self.txa();
+            }
 
-                "BRK" => {
-                    return;
-                    // TODO Handle BRK Interrupt
-                    /*self.program_counter += 1;
-                    if self.mem_read(interrupt::BRK.vector_addr) == 0x00 {
-                        // self.program_counter
-                        return;
-                    }
-
-                    if self.status & 0b0000_0100 == 0 {
-                        self.interrupt(interrupt::BRK);
-                    }*/
-                }
+            "TXS" => {
+                self.txs();
+            }
 
-                // Undocumented Instructions
-                "*DCP" => {
-                    self.dec(&instruction.addr);
-                    self.cmp(&instruction.addr);
-                }
+            "TYA" => {
+                self.tya();
+            }
 
-                "*ISB" => {
-                    self.inc(&instruction.addr);
-                    self.sbc(&instruction.addr);
+            "BRK" => {
+                return false;
+                // TODO Handle BRK Interrupt
+                /*self.program_counter += 1;
+                if self.mem_read(interrupt::BRK.vector_addr) == 0x00 {
+                    // self.program_counter
+                    return;
                 }
 
-                "*LAX" => {
-                    self.lda(&instruction.addr);
-                    self.ldx(&instruction.addr);
-                }
+                if self.status & 0b0000_0100 == 0 {
+                    self.interrupt(interrupt::BRK);
+                }*/
+            }
 
-                "*NOP" => {}
+            // Undocumented Instructions
+            "*DCP" => {
+                self.dec(&instruction.addr);
+                self.cmp(&instruction.addr);
+            }
 
-                "*RLA" => {
-                    self.rol(&instruction.addr);
-                    self.and(&instruction.addr);
-                }
+            "*ISB" => {
+                self.inc(&instruction.addr);
+                self.sbc(&instruction.addr);
+            }
 
-                "*RRA" => {
-                    self.ror(&instruction.addr);
-                    self.adc(&instruction.addr);
-                }
+            "*LAX" => {
+                self.lda(&instruction.addr);
+                self.ldx(&instruction.addr);
+            }
 
-                "*SAX" => {
-                    self.sax(&instruction.addr);
-                }
+            "*NOP" => {}
 
-                "*SBC" => {
-                    self.sbc(&instruction.addr);
-                }
+            "*RLA" => {
+                self.rol(&instruction.addr);
+                self.and(&instruction.addr);
+            }
 
-                "*SLO" => {
-                    self.asl(&instruction.addr);
-                    self.ora(&instruction.addr);
-                }
+            "*RRA" => {
+                self.ror(&instruction.addr);
+                self.adc(&instruction.addr);
+            }
 
-                "*SRE" => {
-                    self.lsr(&instruction.addr);
-                    self.eor(&instruction.addr);
-                }
+            "*SAX" => {
+                self.sax(&instruction.addr);
+            }
 
-                _ => todo!(),
+            "*SBC" => {
+                self.sbc(&instruction.addr);
             }
 
-            self.bus.tick(instruction.cycles);
+            "*SLO" => {
+                self.asl(&instruction.addr);
+                self.ora(&instruction.addr);
+            }
 
-            if program_counter_state == self.program_counter {
-                self.program_counter += (instruction.len - 1) as u16;
+            "*SRE" => {
+                self.lsr(&instruction.addr);
+                self.eor(&instruction.addr);
             }
+
+            _ => todo!(),
         }
+
+        self.bus.tick(instruction.cycles);
+        self.bus.run_oam_dma();
+
+        if program_counter_state == self.program_counter {
+            self.program_counter += (instruction.len - 1) as u16;
+        }
+
+        true
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{joypad, ppu::NesPPU, rom::test};
+    use crate::rom::test;
 
     use super::*;
 
     #[test]
     fn test_0xa9_lda_immediate_load_data() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x05, 0x00]);
         assert_eq!(cpu.register_a, 0x05);
@@ -1139,10 +1201,7 @@ mod test {
 
     #[test]
     fn test_0xa9_lda_zero_flag() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x00, 0x00]);
         assert!(cpu.status & 0b0000_0010 == 0b10);
@@ -1150,10 +1209,7 @@ mod test {
 
     #[test]
     fn test_0xaa_tax_move_a_to_x() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x0a, 0xaa, 0x00]);
 
@@ -1162,10 +1218,7 @@ mod test {
 
     #[test]
     fn test_5_ops_working_together() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xc0, 0xaa, 0xe8, 0x00]);
 
@@ -1174,10 +1227,7 @@ mod test {
 
     #[test]
     fn test_inx_overflow() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0xff, 0xaa, 0xe8, 0xe8, 0x00]);
 
@@ -1186,10 +1236,7 @@ mod test {
 
     #[test]
     fn test_lda_from_memory() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.mem_write(0x10, 0x55);
 
@@ -1200,10 +1247,7 @@ mod test {
 
     #[test]
     fn test_adc_immediate_basic_addition() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.register_a = 0x05;
         cpu.load_and_run(vec![0xA9, 0x05, 0x69, 0x03, 0x00]); // LDA #$05 ADC #$03 BRK
@@ -1216,10 +1260,7 @@ mod test {
 
     #[test]
     fn test_adc_with_carry_set() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0xFF, 0x69, 0x01, 0xA9, 0x05, 0x69, 0x03, 0x00]); // LDA #$FF ADC #$01 LDA #$05 ADC #$03 BRK
 
@@ -1229,10 +1270,7 @@ mod test {
 
     #[test]
     fn test_adc_overflow() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x50, 0x69, 0x50, 0x00]); // LDA #$50 ADC #$50 BRK
 
@@ -1243,10 +1281,7 @@ mod test {
 
     #[test]
     fn test_and() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x11, 0x29, 0x10, 0x00]); // LDA $#11 AND $#10 BRK
 
@@ -1257,10 +1292,7 @@ mod test {
 
     #[test]
     fn test_and_negative_flag() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0xCC, 0x29, 0xAA, 0x00]); // LDA #$CC AND #$AA BRK
 
@@ -1271,10 +1303,7 @@ mod test {
 
     #[test]
     fn test_asl_accumulator() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x4D, 0x0A, 0x00]); // LDA #$4D ASL BRK
 
@@ -1286,10 +1315,7 @@ mod test {
 
     #[test]
     fn test_asl_zero_page() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.mem_write(0x10, 0x81);
         cpu.load_and_run(vec![0x06, 0x10, 0x00]); // ASL $10 BRK
@@ -1302,10 +1328,7 @@ mod test {
 
     #[test]
     fn test_0x24_bit_zero_flag_set() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.mem_write(0x10, 0x92); // 0b1001_0010 so negative should be set
         cpu.load_and_run(vec![0xA9, 0x00, 0x24, 0x10, 0x00]); // LDA #$00, BIT $10 BRK
@@ -1317,10 +1340,7 @@ mod test {
 
     #[test]
     fn test_0x24_bit_zero_flag_clear() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.mem_write(0x10, 0x01); // 0b0000_0001
         cpu.load_and_run(vec![0xA9, 0x01, 0x24, 0x10, 0x00]); // LDA #$01, BIT $10 BRK
@@ -1332,10 +1352,7 @@ mod test {
 
     #[test]
     fn test_sbc_immediate_basic_subtraction() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x10, 0xE9, 0x05, 0x00]); // LDA #$10 SBC #$05 BRK
 
@@ -1347,10 +1364,7 @@ mod test {
 
     #[test]
     fn test_sbc_with_borrow() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x05, 0xE9, 0x10, 0x00]); // LDA #$05 SBC #$10 BRK
 
@@ -1362,10 +1376,7 @@ mod test {
 
     #[test]
     fn test_sbc() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x05, 0xE9, 0x05, 0x00]); // LDA #$05 SBC #$05 BRK
 
@@ -1377,10 +1388,7 @@ mod test {
 
     #[test]
     fn test_ora() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x12, 0x09, 0x08, 0x00]); // LDA #$12 ORA #$08 BRK
 
@@ -1389,10 +1397,7 @@ mod test {
 
     #[test]
     fn test_eor() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x15, 0x49, 0x0F, 0x00]); // LDA #$15 EOR #$0F BRK
 
@@ -1401,10 +1406,7 @@ mod test {
 
     #[test]
     fn test_cmp_equal() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x05, 0xC9, 0x05, 0x00]); // LDA #$05 CMP #$05 BRK
 
@@ -1415,10 +1417,7 @@ mod test {
 
     #[test]
     fn test_lsr_accumulator() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x02, 0x4A, 0x00]); // LDA #$02 LSR BRK
 
@@ -1429,10 +1428,7 @@ mod test {
 
     #[test]
     fn test_lsr_zero_page() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.mem_write(0x10, 0x01);
         cpu.load_and_run(vec![0x46, 0x10, 0x00]); // LSR $10 BRK
@@ -1444,10 +1440,7 @@ mod test {
 
     #[test]
     fn test_rol_accumulator() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x81, 0x2A, 0x00]); // LDA #$81 ROL BRK
 
@@ -1458,10 +1451,7 @@ mod test {
 
     #[test]
     fn test_rol_with_carry_in() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x38, 0xA9, 0x40, 0x2A, 0x00]); // SEC LDA #$40 ROL BRK
 
@@ -1471,10 +1461,7 @@ mod test {
 
     #[test]
     fn test_ror_accumulator() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xa9, 0x01, 0x6a, 0x00]); // LDA #$01 ROR BRK
 
@@ -1485,10 +1472,7 @@ mod test {
 
     #[test]
     fn test_ror_with_carry_in() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x38, 0xa9, 0x02, 0x6A, 0x00]); // SEC LDA #$02 ROR BRK
 
@@ -1499,10 +1483,7 @@ mod test {
 
     #[test]
     fn test_pha_pla() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0xA9, 0x45, 0x48, 0xA9, 0x00, 0x68, 0x00]); // LDA #$45 PHA LDA #$00 PLA BRK
 
@@ -1511,10 +1492,7 @@ mod test {
 
     #[test]
     fn test_jmp_absolute() {
-        let bus = Bus::new(
-            test::test_rom(),
-            |ppu: &NesPPU, joypad: &mut joypad::Joypad| {},
-        );
+        let bus = Bus::new(test::test_rom());
         let mut cpu = CPU::new(bus);
         cpu.load_and_run(vec![0x4C, 0x10, 0x00, 0x00]); // JMP $0010 BRK
 
@@ -1,3 +1,6 @@
+use std::io::{self, Write};
+
+#[derive(Clone)]
 pub struct Frame {
     pub data: Vec<u8>,
 }
@@ -20,4 +23,10 @@ impl Frame {
             self.data[base + 2] = rgb.2;
         }
     }
+
+    // binary PPM, readable by most image tools without extra dependencies
+    pub fn write_ppm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "P6\n{} {}\n255\n", Frame::WIDTH, Frame::HIGHT)?;
+        out.write_all(&self.data)
+    }
 }
@@ -0,0 +1,259 @@
+use crate::frame::Frame;
+use crate::joypad::JoypadButton;
+use crate::nes::Nes;
+use crate::rom::Rom;
+
+// Buttons held per frame. Each line is `<frame> <buttons>`, where buttons are
+// joined with `+` (e.g. `A+RIGHT`) or `-` for none. A line holds its buttons
+// from that frame until the next line; `#` starts a comment.
+pub struct InputScript {
+    events: Vec<(usize, JoypadButton)>,
+}
+
+impl InputScript {
+    pub fn empty() -> Self {
+        InputScript { events: vec![] }
+    }
+
+    pub fn parse(text: &str) -> Result<InputScript, String> {
+        let mut events: Vec<(usize, JoypadButton)> = vec![];
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let frame = parts
+                .next()
+                .and_then(|frame| frame.parse::<usize>().ok())
+                .ok_or_else(|| format!("line {}: expected a frame number", index + 1))?;
+            let buttons = match parts.next() {
+                Some("-") | None => JoypadButton::empty(),
+                Some(names) => {
+                    let mut buttons = JoypadButton::empty();
+                    for name in names.split('+') {
+                        buttons |= parse_button(name).ok_or_else(|| {
+                            format!("line {}: unknown button '{}'", index + 1, name)
+                        })?;
+                    }
+                    buttons
+                }
+            };
+            if parts.next().is_some() {
+                return Err(format!("line {}: unexpected trailing input", index + 1));
+            }
+            if events.last().is_some_and(|&(last, _)| last >= frame) {
+                return Err(format!("line {}: frames must be increasing", index + 1));
+            }
+
+            events.push((frame, buttons));
+        }
+
+        Ok(InputScript { events })
+    }
+
+    pub fn buttons_at(&self, frame: usize) -> JoypadButton {
+        self.events
+            .iter()
+            .take_while(|&&(start, _)| start <= frame)
+            .last()
+            .map_or(JoypadButton::empty(), |&(_, buttons)| buttons)
+    }
+}
+
+fn parse_button(name: &str) -> Option<JoypadButton> {
+    match name.to_ascii_uppercase().as_str() {
+        "A" => Some(JoypadButton::BUTTON_A),
+        "B" => Some(JoypadButton::BUTTON_B),
+        "SELECT" => Some(JoypadButton::SELECT),
+        "START" => Some(JoypadButton::START),
+        "UP" => Some(JoypadButton::UP),
+        "DOWN" => Some(JoypadButton::DOWN),
+        "LEFT" => Some(JoypadButton::LEFT),
+        "RIGHT" => Some(JoypadButton::RIGHT),
+        _ => None,
+    }
+}
+
+// Checked once per frame against the CPU address space, e.g. `6000!=80` for
+// test ROMs that report their status at $6000. Checking must not disturb the
+// game, so only RAM, PRG-RAM and PRG-ROM can be watched, not I/O registers.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StopCondition {
+    Equals(u16, u8),
+    NotEquals(u16, u8),
+}
+
+impl StopCondition {
+    pub fn parse(text: &str) -> Result<StopCondition, String> {
+        let (addr, value, equals) = if let Some((addr, value)) = text.split_once("==") {
+            (addr, value, true)
+        } else if let Some((addr, value)) = text.split_once("!=") {
+            (addr, value, false)
+        } else {
+            return Err(format!("condition '{}' needs == or !=", text));
+        };
+
+        let addr = parse_hex(addr)
+            .and_then(|addr| u16::try_from(addr).ok())
+            .ok_or_else(|| format!("bad address '{}'", addr))?;
+        if (0x2000..0x6000).contains(&addr) {
+            return Err(format!("address {:04X} is an I/O register", addr));
+        }
+        let value = parse_hex(value)
+            .and_then(|value| u8::try_from(value).ok())
+            .ok_or_else(|| format!("bad value '{}'", value))?;
+
+        Ok(if equals {
+            StopCondition::Equals(addr, value)
+        } else {
+            StopCondition::NotEquals(addr, value)
+        })
+    }
+
+    fn is_met(&self, nes: &Nes) -> bool {
+        match *self {
+            StopCondition::Equals(addr, value) => nes.peek(addr) == Some(value),
+            StopCondition::NotEquals(addr, value) => nes.peek(addr) != Some(value),
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix('$'))
+        .unwrap_or(text);
+    u32::from_str_radix(digits, 16).ok()
+}
+
+pub struct Outcome {
+    pub frames: usize,
+    pub condition_met: bool,
+    pub frame: Frame,
+    pub ram: Vec<u8>,
+}
+
+// Runs until `max_frames` frames have completed or `until` holds at a frame
+// boundary, whichever comes first.
+pub fn run(
+    rom: Rom,
+    max_frames: usize,
+    until: Option<StopCondition>,
+    input: &InputScript,
+) -> Outcome {
+    let mut nes = Nes::new(rom);
+    let mut condition_met = false;
+
+    while nes.frame_count() < max_frames {
+        nes.set_buttons(input.buttons_at(nes.frame_count()));
+        if nes.run_frame().is_none() {
+            break;
+        }
+
+        if let Some(condition) = until {
+            if condition.is_met(&nes) {
+                condition_met = true;
+                break;
+            }
+        }
+    }
+
+    Outcome {
+        frames: nes.frame_count(),
+        condition_met,
+        frame: nes.frame().clone(),
+        ram: nes.ram().to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+    use crate::rom::Mirroring;
+
+    // Enables the vblank NMI and spins. The NMI handler counts frames in $10
+    // and copies the A button from the controller into $11.
+    fn nmi_counter_rom() -> Rom {
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+            0x4C, 0x05, 0x80, // loop: JMP loop
+        ];
+        let nmi_handler = [
+            0xE6, 0x10, // INC $10
+            0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #$01, STA $4016
+            0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+            0xAD, 0x16, 0x40, 0x29, 0x01, 0x85, 0x11, // LDA $4016, AND #$01, STA $11
+            0x40, // RTI
+        ];
+        prg_rom[0..program.len()].copy_from_slice(&program);
+        prg_rom[0x20..0x20 + nmi_handler.len()].copy_from_slice(&nmi_handler);
+        prg_rom[0x7FFA..].copy_from_slice(&[0x20, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        test::test_rom_with(prg_rom, vec![0; 0x2000], 0, Mirroring::HORIZONTAL)
+    }
+
+    #[test]
+    fn test_runs_fixed_number_of_frames() {
+        let outcome = run(nmi_counter_rom(), 10, None, &InputScript::empty());
+        assert_eq!(outcome.frames, 10);
+        assert!(!outcome.condition_met);
+        assert_eq!(outcome.ram.len(), 2048);
+        assert!((9..=10).contains(&outcome.ram[0x10]));
+    }
+
+    #[test]
+    fn test_stops_when_condition_is_met() {
+        let until = StopCondition::parse("10==04").unwrap();
+        let outcome = run(nmi_counter_rom(), 100, Some(until), &InputScript::empty());
+        assert!(outcome.condition_met);
+        assert_eq!(outcome.ram[0x10], 4);
+        assert!(outcome.frames < 10);
+    }
+
+    #[test]
+    fn test_scripted_input_reaches_the_game() {
+        let input = InputScript::parse("0 -\n5 A+RIGHT\n").unwrap();
+        let until = StopCondition::parse("$11!=0").unwrap();
+        let outcome = run(nmi_counter_rom(), 100, Some(until), &input);
+        assert!(outcome.condition_met);
+        assert!(outcome.frames >= 5 && outcome.frames <= 7);
+    }
+
+    #[test]
+    fn test_input_script_parsing() {
+        let input = InputScript::parse("# intro\n10 START\n\n12 -  # release\n20 b+up\n").unwrap();
+        assert_eq!(input.buttons_at(0), JoypadButton::empty());
+        assert_eq!(input.buttons_at(11), JoypadButton::START);
+        assert_eq!(input.buttons_at(12), JoypadButton::empty());
+        assert_eq!(
+            input.buttons_at(500),
+            JoypadButton::BUTTON_B | JoypadButton::UP
+        );
+
+        assert!(InputScript::parse("10 JUMP").is_err());
+        assert!(InputScript::parse("start A").is_err());
+        assert!(InputScript::parse("10 A\n5 B").is_err());
+    }
+
+    #[test]
+    fn test_stop_condition_parsing() {
+        assert_eq!(
+            StopCondition::parse("0x6000!=80"),
+            Ok(StopCondition::NotEquals(0x6000, 0x80))
+        );
+        assert_eq!(
+            StopCondition::parse("10==ff"),
+            Ok(StopCondition::Equals(0x10, 0xFF))
+        );
+        assert!(StopCondition::parse("6000=80").is_err());
+        assert!(StopCondition::parse("6000==100").is_err());
+        assert!(StopCondition::parse("2002!=0").is_err());
+        assert!(StopCondition::parse("4016==1").is_err());
+    }
+}
@@ -1,8 +1,10 @@
 use bitflags::bitflags;
 
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
 bitflags! {
     // https://wiki.nesdev.com/w/index.php/Controller_reading_code
-    #[derive(Copy, Clone)]
+    #[derive(Debug, PartialEq, Copy, Clone)]
     pub struct JoypadButton: u8 {
         const RIGHT             = 0b10000000;
         const LEFT              = 0b01000000;
@@ -53,3 +55,18 @@ impl Joypad {
         self.button_status.set(key, enable);
     }
 }
+
+impl Snapshot for Joypad {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bool(self.strobe);
+        writer.write_u8(self.button_index);
+        writer.write_u8(self.button_status.bits());
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.strobe = reader.read_bool()?;
+        self.button_index = reader.read_u8()?;
+        self.button_status = JoypadButton::from_bits_retain(reader.read_u8()?);
+        Ok(())
+    }
+}
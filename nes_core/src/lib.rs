@@ -0,0 +1,23 @@
+pub mod apu;
+pub mod apu_channels;
+pub mod bus;
+pub mod cpu;
+pub mod frame;
+pub mod headless;
+pub mod interrupt;
+pub mod joypad;
+pub mod mapper;
+pub mod mappers;
+pub mod nes;
+pub mod opcode;
+pub mod palette;
+pub mod ppu;
+pub mod ppu_registers;
+pub mod render;
+pub mod rewind;
+pub mod rom;
+pub mod save_file;
+pub mod save_state;
+pub mod trace;
+
+extern crate lazy_static;
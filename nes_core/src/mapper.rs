@@ -0,0 +1,67 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    mappers::{axrom::Axrom, cnrom::Cnrom, mmc1::Mmc1, mmc3::Mmc3, nrom::Nrom, uxrom::Uxrom},
+    rom::{Mirroring, Rom},
+    save_state::Snapshot,
+};
+
+// Snapshot covers the banking registers and any cartridge RAM
+pub trait Mapper: Snapshot {
+    // CPU side of the cartridge ($8000-$FFFF)
+    fn read_prg(&self, addr: u16) -> u8;
+
+    fn write_prg(&mut self, addr: u16, data: u8);
+
+    // Cartridge work RAM ($6000-$7FFF), absent on most boards
+    fn read_prg_ram(&self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn write_prg_ram(&mut self, _addr: u16, _data: u8) {}
+
+    // Battery-backed PRG-RAM contents, None when the cartridge has no battery
+    fn save_data(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn load_save_data(&mut self, _data: &[u8]) {}
+
+    // PPU side of the cartridge ($0000-$1FFF)
+    fn read_chr(&self, addr: u16) -> u8;
+
+    fn write_chr(&mut self, addr: u16, data: u8);
+
+    fn mirroring(&self) -> Mirroring;
+
+    // Extra nametable RAM on four-screen boards, backing $2800-$2FFF; `addr`
+    // is the offset into those 2KB
+    fn read_nametable(&self, _addr: u16) -> u8 {
+        0
+    }
+
+    fn write_nametable(&mut self, _addr: u16, _data: u8) {}
+
+    // Pattern table address put on the PPU bus, for mappers snooping A12
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    fn irq_pending(&self) -> bool {
+        false
+    }
+}
+
+pub fn is_supported(mapper: u16) -> bool {
+    matches!(mapper, 0 | 1 | 2 | 3 | 4 | 7)
+}
+
+pub fn new_mapper(rom: Rom) -> Rc<RefCell<dyn Mapper>> {
+    match rom.mapper {
+        0 => Rc::new(RefCell::new(Nrom::new(rom))),
+        1 => Rc::new(RefCell::new(Mmc1::new(rom))),
+        2 => Rc::new(RefCell::new(Uxrom::new(rom))),
+        3 => Rc::new(RefCell::new(Cnrom::new(rom))),
+        4 => Rc::new(RefCell::new(Mmc3::new(rom))),
+        7 => Rc::new(RefCell::new(Axrom::new(rom))),
+        _ => panic!("Mapper {} is not supported", rom.mapper),
+    }
+}
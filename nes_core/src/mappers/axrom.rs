@@ -0,0 +1,114 @@
+use crate::{
+    mapper::Mapper,
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const PRG_BANK_SIZE: usize = 0x8000;
+
+// Mapper 7: switchable 32K PRG bank, CHR-RAM and single-screen mirroring
+// selected by the bank register
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    // 7  bit  0
+    // ---- ----
+    // xxxM xPPP
+    //    |  |||
+    //    |  +++- Select 32 KB PRG ROM bank for CPU $8000-$FFFF
+    //    +------ Select 1 KB VRAM page for all 4 nametables
+    bank_select: u8,
+}
+
+impl Axrom {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Axrom {
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            bank_select: 0,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let bank_count = (self.prg_rom.len() / PRG_BANK_SIZE).max(1);
+        let bank = (self.bank_select & 0b111) as usize % bank_count;
+        self.prg_rom[(bank * PRG_BANK_SIZE + (addr as usize - 0x8000)) % self.prg_rom.len()]
+    }
+
+    fn write_prg(&mut self, _addr: u16, data: u8) {
+        self.bank_select = data;
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        // CHR smaller than 8K mirrors across the pattern tables
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[addr as usize % len] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.bank_select & 0b1_0000 == 0 {
+            Mirroring::SINGLE_SCREEN_LOWER
+        } else {
+            Mirroring::SINGLE_SCREEN_UPPER
+        }
+    }
+}
+
+impl Snapshot for Axrom {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.bank_select);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.bank_select = reader.read_u8()?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+
+    #[test]
+    fn test_prg_bank_and_mirroring_select() {
+        let mut prg_rom = Vec::new();
+        for bank in 0..4 {
+            prg_rom.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        let mut axrom = Axrom::new(test::test_rom_with(
+            prg_rom,
+            vec![],
+            7,
+            Mirroring::HORIZONTAL,
+        ));
+
+        assert_eq!(axrom.read_prg(0xFFFF), 0);
+        assert_eq!(axrom.mirroring(), Mirroring::SINGLE_SCREEN_LOWER);
+
+        axrom.write_prg(0x8000, 0b1_0010);
+        assert_eq!(axrom.read_prg(0x8000), 2);
+        assert_eq!(axrom.mirroring(), Mirroring::SINGLE_SCREEN_UPPER);
+    }
+}
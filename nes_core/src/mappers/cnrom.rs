@@ -0,0 +1,153 @@
+use crate::{
+    mapper::Mapper,
+    mappers::nametable_ram::NametableRam,
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const CHR_BANK_SIZE: usize = 0x2000;
+
+// Mapper 3: fixed 16K or 32K PRG-ROM, switchable 8K CHR-ROM bank
+pub struct Cnrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: Mirroring,
+    nametable_ram: NametableRam,
+    chr_bank: u8,
+}
+
+impl Cnrom {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Cnrom {
+            nametable_ram: NametableRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+            chr_bank: 0,
+        }
+    }
+}
+
+impl Cnrom {
+    // images with less than 8K of CHR mirror it across the bank
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank_count = (self.chr.len() / CHR_BANK_SIZE).max(1);
+        let bank = self.chr_bank as usize % bank_count;
+        (bank * CHR_BANK_SIZE + addr as usize) % self.chr.len()
+    }
+}
+
+impl Mapper for Cnrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        // 16K images are mirrored at $C000
+        self.prg_rom[(addr as usize - 0x8000) % self.prg_rom.len()]
+    }
+
+    fn write_prg(&mut self, _addr: u16, data: u8) {
+        self.chr_bank = data;
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn read_nametable(&self, addr: u16) -> u8 {
+        self.nametable_ram.read(addr)
+    }
+
+    fn write_nametable(&mut self, addr: u16, data: u8) {
+        self.nametable_ram.write(addr, data);
+    }
+}
+
+impl Snapshot for Cnrom {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.chr_bank);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+        self.nametable_ram.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.chr_bank = reader.read_u8()?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        self.nametable_ram.load_state(reader)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+
+    #[test]
+    fn test_chr_bank_switching() {
+        let mut chr_rom = Vec::new();
+        for bank in 0..4 {
+            chr_rom.extend(vec![bank; CHR_BANK_SIZE]);
+        }
+        let mut cnrom = Cnrom::new(test::test_rom_with(
+            vec![1; 0x4000],
+            chr_rom,
+            3,
+            Mirroring::HORIZONTAL,
+        ));
+
+        assert_eq!(cnrom.read_chr(0x0000), 0);
+        cnrom.write_prg(0x8000, 2);
+        assert_eq!(cnrom.read_chr(0x0000), 2);
+        assert_eq!(cnrom.read_chr(0x1FFF), 2);
+        assert_eq!(cnrom.read_prg(0xC000), 1);
+    }
+
+    #[test]
+    fn test_chr_ram_without_chr_rom() {
+        let mut cnrom = Cnrom::new(test::test_rom_with(
+            vec![1; 0x4000],
+            vec![],
+            3,
+            Mirroring::HORIZONTAL,
+        ));
+        cnrom.write_prg(0x8000, 3);
+        assert_eq!(cnrom.read_chr(0x1FFF), 0);
+        cnrom.write_chr(0x1FFF, 0x42);
+        assert_eq!(cnrom.read_chr(0x1FFF), 0x42);
+    }
+
+    #[test]
+    fn test_chr_rom_smaller_than_a_bank() {
+        let mut cnrom = Cnrom::new(test::test_rom_with(
+            vec![1; 0x4000],
+            vec![7; 0x1000],
+            3,
+            Mirroring::HORIZONTAL,
+        ));
+        cnrom.write_prg(0x8000, 1);
+        assert_eq!(cnrom.read_chr(0x1FFF), 7);
+        // CHR-ROM stays read-only
+        cnrom.write_chr(0x0000, 0x42);
+        assert_eq!(cnrom.read_chr(0x0000), 7);
+    }
+}
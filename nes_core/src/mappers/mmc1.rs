@@ -0,0 +1,318 @@
+use crate::{
+    mapper::Mapper,
+    mappers::prg_ram::PrgRam,
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+
+// Mapper 1 (SxROM): banking registers are loaded one bit at a time through a
+// 5-bit serial shift register
+pub struct Mmc1 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: PrgRam,
+
+    shift_register: u8,
+    // 4bit0
+    // -----
+    // CPPMM
+    // |||||
+    // |||++- Mirroring (0: one-screen, lower bank; 1: one-screen, upper bank;
+    // |||               2: vertical; 3: horizontal)
+    // |++--- PRG ROM bank mode (0, 1: switch 32 KB at $8000, ignoring low bit of bank number;
+    // |                         2: fix first bank at $8000 and switch 16 KB bank at $C000;
+    // |                         3: fix last bank at $C000 and switch 16 KB bank at $8000)
+    // +----- CHR ROM bank mode (0: switch 8 KB at a time; 1: switch two separate 4 KB banks)
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+}
+
+impl Mmc1 {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Mmc1 {
+            prg_ram: PrgRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            shift_register: 0b1_0000,
+            control: 0b0_1100,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.control = data,
+            0xA000..=0xBFFF => self.chr_bank_0 = data,
+            0xC000..=0xDFFF => self.chr_bank_1 = data,
+            0xE000..=0xFFFF => self.prg_bank = data,
+            _ => unreachable!(),
+        }
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b1_0000 == 0
+    }
+
+    // at least 1, so images under 16K still mirror their single bank
+    fn prg_bank_count(&self) -> usize {
+        (self.prg_rom.len() / PRG_BANK_SIZE).max(1)
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let bank = (self.prg_bank & 0b1111) as usize;
+        let last_bank = self.prg_bank_count() - 1;
+        let offset = (addr as usize) & (PRG_BANK_SIZE - 1);
+
+        let bank = match ((self.control >> 2) & 0b11, addr) {
+            (0 | 1, 0x8000..=0xBFFF) => bank & !1,
+            (0 | 1, _) => bank | 1,
+            (2, 0x8000..=0xBFFF) => 0,
+            (2, _) => bank,
+            (3, 0x8000..=0xBFFF) => bank,
+            (3, _) => last_bank,
+            _ => unreachable!(),
+        };
+
+        ((bank % self.prg_bank_count()) * PRG_BANK_SIZE + offset) % self.prg_rom.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let offset = (addr as usize) & (CHR_BANK_SIZE - 1);
+        let bank = if self.control & 0b1_0000 == 0 {
+            // 8K mode: the low bit of bank 0 is replaced by A12
+            (self.chr_bank_0 & !1) as usize | (addr as usize >> 12)
+        } else if addr < 0x1000 {
+            self.chr_bank_0 as usize
+        } else {
+            self.chr_bank_1 as usize
+        };
+
+        let bank_count = (self.chr.len() / CHR_BANK_SIZE).max(1);
+        ((bank % bank_count) * CHR_BANK_SIZE + offset) % self.chr.len()
+    }
+}
+
+impl Mapper for Mmc1 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        self.prg_rom[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        if data & 0b1000_0000 != 0 {
+            self.shift_register = 0b1_0000;
+            self.control |= 0b0_1100;
+            return;
+        }
+
+        // the register is full once the initial marker bit reaches bit 0
+        let is_full = self.shift_register & 1 == 1;
+        self.shift_register = (self.shift_register >> 1) | ((data & 1) << 4);
+        if is_full {
+            self.write_register(addr, self.shift_register);
+            self.shift_register = 0b1_0000;
+        }
+    }
+
+    fn read_prg_ram(&self, addr: u16) -> u8 {
+        if self.prg_ram_enabled() {
+            self.prg_ram.read(addr)
+        } else {
+            0
+        }
+    }
+
+    fn write_prg_ram(&mut self, addr: u16, data: u8) {
+        if self.prg_ram_enabled() {
+            self.prg_ram.write(addr, data);
+        }
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.prg_ram.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.prg_ram.load_save_data(data);
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0b11 {
+            0 => Mirroring::SINGLE_SCREEN_LOWER,
+            1 => Mirroring::SINGLE_SCREEN_UPPER,
+            2 => Mirroring::VERTICAL,
+            3 => Mirroring::HORIZONTAL,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Snapshot for Mmc1 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.prg_ram.save_state(writer);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+        writer.write_u8(self.shift_register);
+        writer.write_u8(self.control);
+        writer.write_u8(self.chr_bank_0);
+        writer.write_u8(self.chr_bank_1);
+        writer.write_u8(self.prg_bank);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(reader)?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        self.shift_register = reader.read_u8()?;
+        self.control = reader.read_u8()?;
+        self.chr_bank_0 = reader.read_u8()?;
+        self.chr_bank_1 = reader.read_u8()?;
+        self.prg_bank = reader.read_u8()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+
+    fn test_mmc1(prg_banks: u8, chr_banks: u8) -> Mmc1 {
+        let mut prg_rom = Vec::new();
+        for bank in 0..prg_banks {
+            prg_rom.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        let mut chr_rom = Vec::new();
+        for bank in 0..chr_banks {
+            chr_rom.extend(vec![bank; CHR_BANK_SIZE]);
+        }
+        Mmc1::new(test::test_rom_with(
+            prg_rom,
+            chr_rom,
+            1,
+            Mirroring::HORIZONTAL,
+        ))
+    }
+
+    fn serial_write(mmc1: &mut Mmc1, addr: u16, value: u8) {
+        for i in 0..5 {
+            mmc1.write_prg(addr, (value >> i) & 1);
+        }
+    }
+
+    #[test]
+    fn test_power_on_fixes_last_prg_bank() {
+        let mmc1 = test_mmc1(8, 2);
+        assert_eq!(mmc1.read_prg(0x8000), 0);
+        assert_eq!(mmc1.read_prg(0xC000), 7);
+    }
+
+    #[test]
+    fn test_serial_write_switches_prg_bank() {
+        let mut mmc1 = test_mmc1(8, 2);
+        serial_write(&mut mmc1, 0xE000, 5);
+        assert_eq!(mmc1.read_prg(0x8000), 5);
+        assert_eq!(mmc1.read_prg(0xFFFF), 7);
+    }
+
+    #[test]
+    fn test_reset_bit_clears_shift_register() {
+        let mut mmc1 = test_mmc1(8, 2);
+        mmc1.write_prg(0xE000, 1);
+        mmc1.write_prg(0xE000, 1);
+        mmc1.write_prg(0xE000, 0x80);
+        serial_write(&mut mmc1, 0xE000, 2);
+        assert_eq!(mmc1.read_prg(0x8000), 2);
+    }
+
+    #[test]
+    fn test_32k_prg_mode_ignores_low_bit() {
+        let mut mmc1 = test_mmc1(8, 2);
+        serial_write(&mut mmc1, 0x8000, 0b0_0000);
+        serial_write(&mut mmc1, 0xE000, 3);
+        assert_eq!(mmc1.read_prg(0x8000), 2);
+        assert_eq!(mmc1.read_prg(0xC000), 3);
+    }
+
+    #[test]
+    fn test_fixed_first_prg_bank_mode() {
+        let mut mmc1 = test_mmc1(8, 2);
+        serial_write(&mut mmc1, 0x8000, 0b0_1000);
+        serial_write(&mut mmc1, 0xE000, 6);
+        assert_eq!(mmc1.read_prg(0x8000), 0);
+        assert_eq!(mmc1.read_prg(0xC000), 6);
+    }
+
+    #[test]
+    fn test_4k_chr_banks() {
+        let mut mmc1 = test_mmc1(2, 8);
+        serial_write(&mut mmc1, 0x8000, 0b1_1100);
+        serial_write(&mut mmc1, 0xA000, 3);
+        serial_write(&mut mmc1, 0xC000, 6);
+        assert_eq!(mmc1.read_chr(0x0000), 3);
+        assert_eq!(mmc1.read_chr(0x1000), 6);
+    }
+
+    #[test]
+    fn test_8k_chr_bank_ignores_low_bit() {
+        let mut mmc1 = test_mmc1(2, 8);
+        serial_write(&mut mmc1, 0xA000, 5);
+        assert_eq!(mmc1.read_chr(0x0000), 4);
+        assert_eq!(mmc1.read_chr(0x1000), 5);
+    }
+
+    #[test]
+    fn test_mirroring_changes_at_runtime() {
+        let mut mmc1 = test_mmc1(2, 2);
+        serial_write(&mut mmc1, 0x8000, 0b0_1100);
+        assert_eq!(mmc1.mirroring(), Mirroring::SINGLE_SCREEN_LOWER);
+        serial_write(&mut mmc1, 0x8000, 0b0_1101);
+        assert_eq!(mmc1.mirroring(), Mirroring::SINGLE_SCREEN_UPPER);
+        serial_write(&mut mmc1, 0x8000, 0b0_1110);
+        assert_eq!(mmc1.mirroring(), Mirroring::VERTICAL);
+        serial_write(&mut mmc1, 0x8000, 0b0_1111);
+        assert_eq!(mmc1.mirroring(), Mirroring::HORIZONTAL);
+    }
+
+    #[test]
+    fn test_prg_ram_can_be_disabled() {
+        let mut mmc1 = test_mmc1(2, 0);
+        mmc1.write_prg_ram(0x6000, 0x42);
+        assert_eq!(mmc1.read_prg_ram(0x6000), 0x42);
+        serial_write(&mut mmc1, 0xE000, 0b1_0000);
+        assert_eq!(mmc1.read_prg_ram(0x6000), 0);
+    }
+
+    #[test]
+    fn test_chr_ram_is_writable() {
+        let mut mmc1 = test_mmc1(2, 0);
+        mmc1.write_chr(0x1234, 0x42);
+        assert_eq!(mmc1.read_chr(0x1234), 0x42);
+    }
+}
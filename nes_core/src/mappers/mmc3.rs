@@ -0,0 +1,386 @@
+use crate::{
+    mapper::Mapper,
+    mappers::{nametable_ram::NametableRam, prg_ram::PrgRam},
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+// Mapper 4 (TxROM): 8K PRG banks, 1K/2K CHR banks and a scanline counter
+// clocked by rising edges on PPU A12
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: PrgRam,
+    four_screen: bool,
+    nametable_ram: NametableRam,
+
+    // 7  bit  0
+    // ---- ----
+    // CPMx xRRR
+    // |||   |||
+    // |||   +++- Specify which bank register to update on next write to Bank Data register
+    // |||        (0: 2 KB CHR bank at PPU $0000 (or $1000); 1: 2 KB CHR bank at PPU $0800 (or $1800);
+    // |||         2-5: 1 KB CHR bank at PPU $1000-$1C00 (or $0000-$0C00);
+    // |||         6: 8 KB PRG bank at $8000 (or $C000); 7: 8 KB PRG bank at $A000)
+    // ||+------- Nothing on the MMC3
+    // |+-------- PRG ROM bank mode (0: $8000-$9FFF swappable, $C000-$DFFF fixed to second-last bank;
+    // |                             1: $C000-$DFFF swappable, $8000-$9FFF fixed to second-last bank)
+    // +--------- CHR A12 inversion (0: two 2 KB banks at $0000-$0FFF, four 1 KB banks at $1000-$1FFF;
+    //                               1: two 2 KB banks at $1000-$1FFF, four 1 KB banks at $0000-$0FFF)
+    bank_select: u8,
+    bank_registers: [u8; 8],
+    mirroring: Mirroring,
+    prg_ram_enabled: bool,
+    prg_ram_write_protect: bool,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+    last_a12: bool,
+}
+
+impl Mmc3 {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Mmc3 {
+            prg_ram: PrgRam::new(&rom),
+            nametable_ram: NametableRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            four_screen: rom.screen_mirroring == Mirroring::FOUR_SCREEN,
+            bank_select: 0,
+            bank_registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirroring: rom.screen_mirroring,
+            prg_ram_enabled: true,
+            prg_ram_write_protect: false,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            last_a12: false,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        // images under 16K repeat their only bank
+        let bank_count = (self.prg_rom.len() / PRG_BANK_SIZE).max(1);
+        let second_last = bank_count.saturating_sub(2);
+        let prg_mode = self.bank_select & 0b0100_0000 != 0;
+
+        let bank = match (prg_mode, addr) {
+            (false, 0x8000..=0x9FFF) => self.bank_registers[6] as usize,
+            (true, 0x8000..=0x9FFF) => second_last,
+            (_, 0xA000..=0xBFFF) => self.bank_registers[7] as usize,
+            (false, 0xC000..=0xDFFF) => second_last,
+            (true, 0xC000..=0xDFFF) => self.bank_registers[6] as usize,
+            _ => bank_count - 1,
+        };
+
+        ((bank % bank_count) * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1)))
+            % self.prg_rom.len()
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        // with A12 inversion the 2K and 1K halves of the pattern tables swap places
+        let addr = if self.bank_select & 0b1000_0000 != 0 {
+            addr ^ 0x1000
+        } else {
+            addr
+        } as usize;
+
+        let bank = match addr {
+            0x0000..=0x07FF => (self.bank_registers[0] & !1) as usize | (addr >> 10 & 1),
+            0x0800..=0x0FFF => (self.bank_registers[1] & !1) as usize | (addr >> 10 & 1),
+            _ => self.bank_registers[2 + ((addr - 0x1000) >> 10)] as usize,
+        };
+
+        let bank_count = (self.chr.len() / CHR_BANK_SIZE).max(1);
+        ((bank % bank_count) * CHR_BANK_SIZE + (addr & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn read_prg(&self, addr: u16) -> u8 {
+        self.prg_rom[self.prg_offset(addr)]
+    }
+
+    fn write_prg(&mut self, addr: u16, data: u8) {
+        let even = addr & 1 == 0;
+        match (addr, even) {
+            (0x8000..=0x9FFF, true) => self.bank_select = data,
+            (0x8000..=0x9FFF, false) => {
+                self.bank_registers[(self.bank_select & 0b111) as usize] = data;
+            }
+            (0xA000..=0xBFFF, true) => {
+                if !self.four_screen {
+                    self.mirroring = if data & 1 == 0 {
+                        Mirroring::VERTICAL
+                    } else {
+                        Mirroring::HORIZONTAL
+                    };
+                }
+            }
+            (0xA000..=0xBFFF, false) => {
+                self.prg_ram_enabled = data & 0b1000_0000 != 0;
+                self.prg_ram_write_protect = data & 0b0100_0000 != 0;
+            }
+            (0xC000..=0xDFFF, true) => self.irq_latch = data,
+            (0xC000..=0xDFFF, false) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, true) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (_, false) => self.irq_enabled = true,
+        }
+    }
+
+    fn read_prg_ram(&self, addr: u16) -> u8 {
+        if self.prg_ram_enabled {
+            self.prg_ram.read(addr)
+        } else {
+            0
+        }
+    }
+
+    fn write_prg_ram(&mut self, addr: u16, data: u8) {
+        if self.prg_ram_enabled && !self.prg_ram_write_protect {
+            self.prg_ram.write(addr, data);
+        }
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.prg_ram.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.prg_ram.load_save_data(data);
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        self.chr[self.chr_offset(addr)]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let offset = self.chr_offset(addr);
+            self.chr[offset] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn read_nametable(&self, addr: u16) -> u8 {
+        self.nametable_ram.read(addr)
+    }
+
+    fn write_nametable(&mut self, addr: u16, data: u8) {
+        self.nametable_ram.write(addr, data);
+    }
+
+    fn ppu_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 && !self.last_a12 {
+            self.clock_irq_counter();
+        }
+        self.last_a12 = a12;
+    }
+
+    fn irq_pending(&self) -> bool {
+        self.irq_pending
+    }
+}
+
+impl Snapshot for Mmc3 {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.prg_ram.save_state(writer);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+        self.nametable_ram.save_state(writer);
+        writer.write_u8(self.bank_select);
+        writer.write_bytes(&self.bank_registers);
+        writer.write_bool(self.mirroring == Mirroring::HORIZONTAL);
+        writer.write_bool(self.prg_ram_enabled);
+        writer.write_bool(self.prg_ram_write_protect);
+        writer.write_u8(self.irq_latch);
+        writer.write_u8(self.irq_counter);
+        writer.write_bool(self.irq_reload);
+        writer.write_bool(self.irq_enabled);
+        writer.write_bool(self.irq_pending);
+        writer.write_bool(self.last_a12);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(reader)?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        self.nametable_ram.load_state(reader)?;
+        self.bank_select = reader.read_u8()?;
+        reader.read_bytes(&mut self.bank_registers)?;
+        let horizontal = reader.read_bool()?;
+        if !self.four_screen {
+            self.mirroring = if horizontal {
+                Mirroring::HORIZONTAL
+            } else {
+                Mirroring::VERTICAL
+            };
+        }
+        self.prg_ram_enabled = reader.read_bool()?;
+        self.prg_ram_write_protect = reader.read_bool()?;
+        self.irq_latch = reader.read_u8()?;
+        self.irq_counter = reader.read_u8()?;
+        self.irq_reload = reader.read_bool()?;
+        self.irq_enabled = reader.read_bool()?;
+        self.irq_pending = reader.read_bool()?;
+        self.last_a12 = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+
+    fn test_mmc3() -> Mmc3 {
+        let mut prg_rom = Vec::new();
+        for bank in 0..16 {
+            prg_rom.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        let mut chr_rom = Vec::new();
+        for bank in 0..32 {
+            chr_rom.extend(vec![bank; CHR_BANK_SIZE]);
+        }
+        Mmc3::new(test::test_rom_with(
+            prg_rom,
+            chr_rom,
+            4,
+            Mirroring::VERTICAL,
+        ))
+    }
+
+    fn scanline(mmc3: &mut Mmc3) {
+        mmc3.ppu_address(0x0000);
+        mmc3.ppu_address(0x1000);
+    }
+
+    #[test]
+    fn test_prg_bank_modes() {
+        let mut mmc3 = test_mmc3();
+        mmc3.write_prg(0x8000, 6);
+        mmc3.write_prg(0x8001, 3);
+        mmc3.write_prg(0x8000, 7);
+        mmc3.write_prg(0x8001, 4);
+        assert_eq!(mmc3.read_prg(0x8000), 3);
+        assert_eq!(mmc3.read_prg(0xA000), 4);
+        assert_eq!(mmc3.read_prg(0xC000), 14);
+        assert_eq!(mmc3.read_prg(0xE000), 15);
+
+        mmc3.write_prg(0x8000, 0b0100_0110);
+        assert_eq!(mmc3.read_prg(0x8000), 14);
+        assert_eq!(mmc3.read_prg(0xC000), 3);
+    }
+
+    #[test]
+    fn test_prg_smaller_than_two_banks() {
+        for prg_size in [PRG_BANK_SIZE, 0x1000] {
+            let prg_rom: Vec<u8> = (0..prg_size).map(|i| (i >> 8) as u8).collect();
+            let mut mmc3 = Mmc3::new(test::test_rom_with(prg_rom, vec![], 4, Mirroring::VERTICAL));
+            for mode in [0, 0b0100_0000] {
+                mmc3.write_prg(0x8000, mode | 6);
+                mmc3.write_prg(0x8001, 3);
+                for addr in [0x8000, 0xA100, 0xC200, 0xE300] {
+                    let expected = ((addr as usize % PRG_BANK_SIZE % prg_size) >> 8) as u8;
+                    assert_eq!(mmc3.read_prg(addr), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_chr_a12_inversion() {
+        let mut mmc3 = test_mmc3();
+        mmc3.write_prg(0x8000, 0);
+        mmc3.write_prg(0x8001, 8);
+        mmc3.write_prg(0x8000, 5);
+        mmc3.write_prg(0x8001, 20);
+        assert_eq!(mmc3.read_chr(0x0000), 8);
+        assert_eq!(mmc3.read_chr(0x0400), 9);
+        assert_eq!(mmc3.read_chr(0x1C00), 20);
+
+        mmc3.write_prg(0x8000, 0b1000_0000);
+        assert_eq!(mmc3.read_chr(0x1000), 8);
+        assert_eq!(mmc3.read_chr(0x0C00), 20);
+    }
+
+    #[test]
+    fn test_mirroring_register() {
+        let mut mmc3 = test_mmc3();
+        mmc3.write_prg(0xA000, 1);
+        assert_eq!(mmc3.mirroring(), Mirroring::HORIZONTAL);
+        mmc3.write_prg(0xA000, 0);
+        assert_eq!(mmc3.mirroring(), Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_scanline_irq() {
+        let mut mmc3 = test_mmc3();
+        mmc3.write_prg(0xC000, 3);
+        mmc3.write_prg(0xC001, 0);
+        mmc3.write_prg(0xE001, 0);
+
+        scanline(&mut mmc3); // reload to 3
+        scanline(&mut mmc3);
+        scanline(&mut mmc3);
+        assert!(!mmc3.irq_pending());
+        scanline(&mut mmc3);
+        assert!(mmc3.irq_pending());
+
+        mmc3.write_prg(0xE000, 0);
+        assert!(!mmc3.irq_pending());
+    }
+
+    #[test]
+    fn test_irq_needs_a12_rising_edge() {
+        let mut mmc3 = test_mmc3();
+        mmc3.write_prg(0xC000, 0);
+        mmc3.write_prg(0xE001, 0);
+        mmc3.ppu_address(0x1000);
+        assert!(mmc3.irq_pending());
+        mmc3.write_prg(0xE000, 0);
+        mmc3.write_prg(0xE001, 0);
+        mmc3.ppu_address(0x1FFF);
+        assert!(!mmc3.irq_pending());
+    }
+}
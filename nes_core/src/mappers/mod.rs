@@ -0,0 +1,8 @@
+pub mod axrom;
+pub mod cnrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod nametable_ram;
+pub mod nrom;
+pub mod prg_ram;
+pub mod uxrom;
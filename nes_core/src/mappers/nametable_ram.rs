@@ -0,0 +1,48 @@
+use crate::{
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const FOUR_SCREEN_RAM_SIZE: usize = 0x800;
+
+// The 2KB four-screen cartridges add next to the console's own nametable RAM,
+// holding the third and fourth nametables; empty on every other board
+pub struct NametableRam {
+    data: Vec<u8>,
+}
+
+impl NametableRam {
+    pub fn new(rom: &Rom) -> Self {
+        NametableRam {
+            data: if rom.screen_mirroring == Mirroring::FOUR_SCREEN {
+                vec![0; FOUR_SCREEN_RAM_SIZE]
+            } else {
+                vec![]
+            },
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+        self.data[addr as usize % FOUR_SCREEN_RAM_SIZE]
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+        self.data[addr as usize % FOUR_SCREEN_RAM_SIZE] = data;
+    }
+}
+
+impl Snapshot for NametableRam {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        reader.read_bytes(&mut self.data)
+    }
+}
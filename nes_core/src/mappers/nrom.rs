@@ -0,0 +1,103 @@
+use crate::{
+    mapper::Mapper,
+    mappers::{nametable_ram::NametableRam, prg_ram::PrgRam},
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+// Mapper 0: 16K or 32K of fixed PRG-ROM and 8K of fixed CHR-ROM (or CHR-RAM)
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    prg_ram: PrgRam,
+    mirroring: Mirroring,
+    nametable_ram: NametableRam,
+}
+
+impl Nrom {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Nrom {
+            prg_ram: PrgRam::new(&rom),
+            nametable_ram: NametableRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        // 16K images (and anything smaller) mirror across $8000-$FFFF
+        self.prg_rom[(addr - 0x8000) as usize % self.prg_rom.len()]
+    }
+
+    // NROM has no registers; writes to ROM are simply lost
+    fn write_prg(&mut self, _addr: u16, _data: u8) {}
+
+    fn read_prg_ram(&self, addr: u16) -> u8 {
+        self.prg_ram.read(addr)
+    }
+
+    fn write_prg_ram(&mut self, addr: u16, data: u8) {
+        self.prg_ram.write(addr, data);
+    }
+
+    fn save_data(&self) -> Option<&[u8]> {
+        self.prg_ram.save_data()
+    }
+
+    fn load_save_data(&mut self, data: &[u8]) {
+        self.prg_ram.load_save_data(data);
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        // CHR smaller than 8K mirrors across the pattern tables
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[addr as usize % len] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn read_nametable(&self, addr: u16) -> u8 {
+        self.nametable_ram.read(addr)
+    }
+
+    fn write_nametable(&mut self, addr: u16, data: u8) {
+        self.nametable_ram.write(addr, data);
+    }
+}
+
+impl Snapshot for Nrom {
+    fn save_state(&self, writer: &mut StateWriter) {
+        self.prg_ram.save_state(writer);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+        self.nametable_ram.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.prg_ram.load_state(reader)?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        self.nametable_ram.load_state(reader)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use crate::{
+    rom::Rom,
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+// Cartridge work RAM mapped at $6000-$7FFF, optionally kept alive by a battery
+pub struct PrgRam {
+    data: Vec<u8>,
+    battery: bool,
+}
+
+impl PrgRam {
+    pub fn new(rom: &Rom) -> Self {
+        PrgRam {
+            data: vec![0; rom.prg_ram_size + rom.prg_nvram_size],
+            battery: rom.battery,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        if self.data.is_empty() {
+            return 0;
+        }
+        self.data[(addr - 0x6000) as usize % self.data.len()]
+    }
+
+    pub fn write(&mut self, addr: u16, data: u8) {
+        if self.data.is_empty() {
+            return;
+        }
+        let len = self.data.len();
+        self.data[(addr - 0x6000) as usize % len] = data;
+    }
+
+    pub fn save_data(&self) -> Option<&[u8]> {
+        if self.battery {
+            Some(&self.data)
+        } else {
+            None
+        }
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        if self.battery {
+            let len = data.len().min(self.data.len());
+            self.data[..len].copy_from_slice(&data[..len]);
+        }
+    }
+}
+
+impl Snapshot for PrgRam {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.data);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        reader.read_bytes(&mut self.data)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::{test, Mirroring};
+
+    #[test]
+    fn test_battery_backed_ram_is_saved() {
+        let mut rom = test::test_rom_with(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::VERTICAL);
+        rom.battery = true;
+        let mut prg_ram = PrgRam::new(&rom);
+
+        prg_ram.write(0x6001, 0x42);
+        assert_eq!(prg_ram.save_data().unwrap()[1], 0x42);
+
+        let mut restored = PrgRam::new(&rom);
+        restored.load_save_data(prg_ram.save_data().unwrap());
+        assert_eq!(restored.read(0x6001), 0x42);
+    }
+
+    #[test]
+    fn test_volatile_ram_is_not_saved() {
+        let rom = test::test_rom_with(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::VERTICAL);
+        let mut prg_ram = PrgRam::new(&rom);
+
+        prg_ram.write(0x7FFF, 0x42);
+        assert_eq!(prg_ram.read(0x7FFF), 0x42);
+        assert!(prg_ram.save_data().is_none());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{
+    mapper::Mapper,
+    mappers::nametable_ram::NametableRam,
+    rom::{Mirroring, Rom},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+// Mapper 2: switchable 16K PRG bank at $8000, last bank fixed at $C000, CHR-RAM
+pub struct Uxrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+    mirroring: Mirroring,
+    nametable_ram: NametableRam,
+    prg_bank: u8,
+}
+
+impl Uxrom {
+    pub fn new(rom: Rom) -> Self {
+        let chr_is_ram = rom.chr_rom.is_empty();
+        Uxrom {
+            nametable_ram: NametableRam::new(&rom),
+            prg_rom: rom.prg_rom,
+            chr: if chr_is_ram {
+                vec![0; rom.chr_ram_size + rom.chr_nvram_size]
+            } else {
+                rom.chr_rom
+            },
+            chr_is_ram,
+            mirroring: rom.screen_mirroring,
+            prg_bank: 0,
+        }
+    }
+
+    // at least 1, so images under 16K still mirror their single bank
+    fn prg_bank_count(&self) -> usize {
+        (self.prg_rom.len() / PRG_BANK_SIZE).max(1)
+    }
+}
+
+impl Mapper for Uxrom {
+    fn read_prg(&self, addr: u16) -> u8 {
+        let bank = match addr {
+            0x8000..=0xBFFF => self.prg_bank as usize % self.prg_bank_count(),
+            _ => self.prg_bank_count() - 1,
+        };
+        let offset = bank * PRG_BANK_SIZE + (addr as usize & (PRG_BANK_SIZE - 1));
+        self.prg_rom[offset % self.prg_rom.len()]
+    }
+
+    fn write_prg(&mut self, _addr: u16, data: u8) {
+        self.prg_bank = data;
+    }
+
+    fn read_chr(&self, addr: u16) -> u8 {
+        // CHR smaller than 8K mirrors across the pattern tables
+        self.chr[addr as usize % self.chr.len()]
+    }
+
+    fn write_chr(&mut self, addr: u16, data: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[addr as usize % len] = data;
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn read_nametable(&self, addr: u16) -> u8 {
+        self.nametable_ram.read(addr)
+    }
+
+    fn write_nametable(&mut self, addr: u16, data: u8) {
+        self.nametable_ram.write(addr, data);
+    }
+}
+
+impl Snapshot for Uxrom {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.prg_bank);
+        if self.chr_is_ram {
+            writer.write_bytes(&self.chr);
+        }
+        self.nametable_ram.save_state(writer);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.prg_bank = reader.read_u8()?;
+        if self.chr_is_ram {
+            reader.read_bytes(&mut self.chr)?;
+        }
+        self.nametable_ram.load_state(reader)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::test;
+
+    fn test_uxrom(prg_banks: u8) -> Uxrom {
+        let mut prg_rom = Vec::new();
+        for bank in 0..prg_banks {
+            prg_rom.extend(vec![bank; PRG_BANK_SIZE]);
+        }
+        Uxrom::new(test::test_rom_with(prg_rom, vec![], 2, Mirroring::VERTICAL))
+    }
+
+    #[test]
+    fn test_prg_bank_switching() {
+        let mut uxrom = test_uxrom(8);
+        assert_eq!(uxrom.read_prg(0x8000), 0);
+        assert_eq!(uxrom.read_prg(0xC000), 7);
+        uxrom.write_prg(0x8000, 3);
+        assert_eq!(uxrom.read_prg(0xBFFF), 3);
+        assert_eq!(uxrom.read_prg(0xFFFF), 7);
+    }
+
+    #[test]
+    fn test_chr_ram_is_writable() {
+        let mut uxrom = test_uxrom(2);
+        uxrom.write_chr(0x1FFF, 0x42);
+        assert_eq!(uxrom.read_chr(0x1FFF), 0x42);
+    }
+
+    #[test]
+    fn test_prg_smaller_than_a_bank() {
+        let mut uxrom = Uxrom::new(test::test_rom_with(
+            vec![5; 0x2000],
+            vec![],
+            2,
+            Mirroring::VERTICAL,
+        ));
+        uxrom.write_prg(0x8000, 3);
+        assert_eq!(uxrom.read_prg(0x8000), 5);
+        assert_eq!(uxrom.read_prg(0xFFFF), 5);
+    }
+
+    #[test]
+    fn test_chr_ram_sized_by_header() {
+        let mut rom = test::test_rom_with(vec![0; 0x8000], vec![], 2, Mirroring::VERTICAL);
+        rom.chr_ram_size = 0x800;
+        let mut uxrom = Uxrom::new(rom);
+        uxrom.write_chr(0x0001, 0x42);
+        assert_eq!(uxrom.read_chr(0x0001), 0x42);
+        assert_eq!(uxrom.read_chr(0x1801), 0x42);
+    }
+}
@@ -0,0 +1,172 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    bus::Bus, cpu::CPU, frame::Frame, joypad::JoypadButton, mapper::Mapper, rom::Rom,
+    save_state::StateError,
+};
+
+// The whole console. The host decides when the emulator runs: one
+// instruction at a time or a frame at a time, with input set in between.
+pub struct Nes {
+    cpu: CPU,
+    halted: bool,
+}
+
+impl Nes {
+    pub fn new(rom: Rom) -> Self {
+        let mut cpu = CPU::new(Bus::new(rom));
+        cpu.reset();
+
+        Nes { cpu, halted: false }
+    }
+
+    pub fn reset(&mut self) {
+        self.cpu.reset();
+        self.halted = false;
+    }
+
+    // returns false once the CPU has stopped on BRK; it stays stopped until
+    // reset or a state is loaded
+    pub fn step_instruction(&mut self) -> bool {
+        if !self.halted && !self.cpu.step() {
+            self.halted = true;
+        }
+        !self.halted
+    }
+
+    // runs until the PPU finishes the next picture and returns it, or None
+    // if the CPU stopped first
+    pub fn run_frame(&mut self) -> Option<&Frame> {
+        let frames = self.cpu.bus.frame_count();
+        while self.cpu.bus.frame_count() == frames {
+            if !self.step_instruction() {
+                return None;
+            }
+        }
+
+        Some(self.cpu.bus.ppu().frame())
+    }
+
+    // the last picture returned by run_frame
+    pub fn frame(&self) -> &Frame {
+        self.cpu.bus.ppu().frame()
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.cpu.bus.frame_count()
+    }
+
+    pub fn set_button(&mut self, button: JoypadButton, pressed: bool) {
+        self.cpu
+            .bus
+            .joypad()
+            .set_button_pressed_status(button, pressed);
+    }
+
+    // replaces every button at once: the ones in `buttons` are held, the
+    // others released
+    pub fn set_buttons(&mut self, buttons: JoypadButton) {
+        let joypad = self.cpu.bus.joypad();
+        joypad.set_button_pressed_status(JoypadButton::all(), false);
+        joypad.set_button_pressed_status(buttons, true);
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        self.cpu.bus.ram()
+    }
+
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        self.cpu.bus.peek(addr)
+    }
+
+    pub fn mapper(&self) -> Rc<RefCell<dyn Mapper>> {
+        self.cpu.bus.mapper()
+    }
+
+    pub fn take_audio_samples(&mut self) -> Vec<i16> {
+        self.cpu.bus.get_audio_samples()
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        self.cpu.save_state()
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        self.cpu.load_state(data)?;
+        self.halted = false;
+        Ok(())
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rom::{test, Mirroring};
+
+    // Enables the vblank NMI, counts NMIs in $10 and stops on BRK after the
+    // third one.
+    fn three_frame_rom() -> Rom {
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+            0xA5, 0x10, 0xC9, 0x03, 0xD0, 0xFA, // loop: LDA $10, CMP #$03, BNE loop
+            0x00, // BRK
+        ];
+        prg_rom[0..program.len()].copy_from_slice(&program);
+        prg_rom[0x20..0x23].copy_from_slice(&[0xE6, 0x10, 0x40]); // INC $10, RTI
+        prg_rom[0x7FFA..].copy_from_slice(&[0x20, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        test::test_rom_with(prg_rom, vec![0; 0x2000], 0, Mirroring::HORIZONTAL)
+    }
+
+    #[test]
+    fn test_run_frame_stops_at_each_frame() {
+        let mut nes = Nes::new(three_frame_rom());
+
+        assert!(nes.run_frame().is_some());
+        assert_eq!(nes.frame_count(), 1);
+        // the NMI for a frame is serviced at the start of the next one
+        assert_eq!(nes.ram()[0x10], 0);
+
+        assert!(nes.run_frame().is_some());
+        assert_eq!(nes.frame_count(), 2);
+        assert_eq!(nes.ram()[0x10], 1);
+    }
+
+    #[test]
+    fn test_halts_on_brk() {
+        let mut nes = Nes::new(three_frame_rom());
+
+        let mut frames = 0;
+        while nes.run_frame().is_some() {
+            frames += 1;
+        }
+        assert_eq!(frames, 3);
+        assert_eq!(nes.ram()[0x10], 3);
+        assert!(!nes.step_instruction());
+
+        nes.reset();
+        assert!(nes.step_instruction());
+    }
+
+    #[test]
+    fn test_buttons_reach_the_joypad() {
+        let mut nes = Nes::new(three_frame_rom());
+        nes.set_buttons(JoypadButton::BUTTON_A | JoypadButton::START);
+        nes.set_button(JoypadButton::BUTTON_A, false);
+
+        let cpu = nes.cpu_mut();
+        cpu.mem_write(0x4016, 1);
+        cpu.mem_write(0x4016, 0);
+        let buttons: Vec<u8> = (0..8).map(|_| cpu.mem_read(0x4016)).collect();
+        assert_eq!(buttons, vec![0, 0, 0, 1, 0, 0, 0, 0]);
+    }
+}
@@ -15,3 +15,20 @@ pub static SYSTEM_PALLETE: [(u8,u8,u8); 64] = [
    (0xFF, 0xEF, 0xA6), (0xFF, 0xF7, 0x9C), (0xD7, 0xE8, 0x95), (0xA6, 0xED, 0xAF), (0xA2, 0xF2, 0xDA),
    (0x99, 0xFF, 0xFC), (0xDD, 0xDD, 0xDD), (0x11, 0x11, 0x11), (0x11, 0x11, 0x11)
 ];
+
+// Each emphasis bit dims the two other channels, by roughly a quarter on NTSC;
+// with all three set the whole picture darkens.
+pub fn emphasise(rgb: (u8, u8, u8), red: bool, green: bool, blue: bool) -> (u8, u8, u8) {
+    let dim = |channel: u8, dimmed: bool| {
+        if dimmed {
+            (channel as u16 * 3 / 4) as u8
+        } else {
+            channel
+        }
+    };
+    (
+        dim(rgb.0, green || blue),
+        dim(rgb.1, red || blue),
+        dim(rgb.2, red || green),
+    )
+}
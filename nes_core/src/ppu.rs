@@ -0,0 +1,1151 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    frame::Frame,
+    mapper::Mapper,
+    mappers::nrom::Nrom,
+    palette,
+    ppu_registers::{
+        control_register::ControlRegister, loopy_register::LoopyRegister,
+        mask_register::MaskRegister, status_register::StatusRegister,
+    },
+    render::{BackgroundShifters, SpriteSlot},
+    rom::{test::test_rom_with, Mirroring},
+    save_state::{Snapshot, StateError, StateReader, StateWriter},
+};
+
+// The PPU data bus holds the last value driven onto it for roughly 600ms
+// before the charge fades to 0; reads of write-only registers return it.
+const IO_LATCH_DECAY_FRAMES: u8 = 36;
+
+pub struct NesPPU {
+    pub mapper: Rc<RefCell<dyn Mapper>>,
+    pub palette_table: [u8; 32],
+    // the console's 2KB of nametable RAM; four-screen cartridges add another
+    // 2KB for the third and fourth nametables
+    vram: [u8; 2048],
+
+    pub ctrl: ControlRegister,
+
+    pub status: StatusRegister,
+    pub mask: MaskRegister,
+    pub loopy: LoopyRegister,
+
+    pub oam_data: [u8; 256],
+    pub oam_addr: u8,
+
+    pub nmi_interrupt: Option<u8>,
+
+    scanline: u16,
+    cycles: usize,
+    internal_data_buf: u8,
+    io_latch: u8,
+    io_latch_age: u8,
+    odd_frame: bool,
+
+    // the next background tile, fetched over 8 dots
+    next_tile: u8,
+    next_palette: u8,
+    next_pattern_lo: u8,
+    next_pattern_hi: u8,
+    shifters: BackgroundShifters,
+
+    secondary_oam: [u8; 32],
+    secondary_oam_count: usize,
+    secondary_has_sprite_zero: bool,
+    line_sprites: Vec<SpriteSlot>,
+    frame: Frame,
+}
+
+impl NesPPU {
+    pub fn new(mapper: Rc<RefCell<dyn Mapper>>) -> Self {
+        NesPPU {
+            mapper,
+            vram: [0; 2048],
+            oam_data: [0; 64 * 4],
+            palette_table: [0; 32],
+            oam_addr: 0,
+            ctrl: ControlRegister::new(),
+            status: StatusRegister::new(),
+            mask: MaskRegister::new(),
+            loopy: LoopyRegister::new(),
+            scanline: 0,
+            cycles: 0,
+            internal_data_buf: 0,
+            io_latch: 0,
+            io_latch_age: 0,
+            odd_frame: false,
+            next_tile: 0,
+            next_palette: 0,
+            next_pattern_lo: 0,
+            next_pattern_hi: 0,
+            shifters: BackgroundShifters::new(),
+            secondary_oam: [0; 32],
+            secondary_oam_count: 0,
+            secondary_has_sprite_zero: false,
+            line_sprites: Vec::new(),
+            frame: Frame::new(),
+            nmi_interrupt: None,
+        }
+    }
+    // private methods
+    fn increment_vram_addr(&mut self) {
+        if self.is_rendering() {
+            // a $2007 access while rendering bumps both scroll counters
+            self.loopy.increment_coarse_x();
+            self.loopy.increment_y();
+        } else {
+            self.loopy.increment(self.ctrl.vram_addr_increment());
+        }
+        self.put_vram_addr_on_bus();
+    }
+
+    // Outside the rendering fetches, v drives the PPU address bus, which
+    // cartridges like MMC3 watch for A12 edges
+    fn put_vram_addr_on_bus(&mut self) {
+        self.mapper.borrow_mut().ppu_address(self.loopy.vram_addr());
+    }
+
+    fn increment_oam_addr(&mut self) {
+        self.oam_addr = self.oam_addr.wrapping_add(1);
+    }
+
+    // Advances the PPU by `cycles` dots. Returns true when a picture has been
+    // completed, which happens as vblank starts.
+    pub fn tick(&mut self, cycles: u8) -> bool {
+        let mut frame_complete = false;
+        for _ in 0..cycles {
+            frame_complete |= self.tick_dot();
+        }
+        frame_complete
+    }
+
+    // Scanlines 0-239 are visible, 241-260 are vblank and 261 is the
+    // pre-render line, which fetches the first tiles of the next frame.
+    fn tick_dot(&mut self) -> bool {
+        let mut frame_complete = false;
+        let visible = self.scanline < 240;
+        let pre_render = self.scanline == 261;
+
+        if self.is_rendering() {
+            self.fetch_background();
+
+            if self.cycles == 65 && visible {
+                self.evaluate_sprites();
+            }
+
+            // background tiles are fetched up to dot 256, sprite tiles for the
+            // next scanline from dot 257 to 320, then background again
+            if self.cycles == 257 {
+                self.fetch_sprites();
+                let sprite_bank = match self.ctrl.sprite_size() {
+                    8 => self.ctrl.sprt_pattern_addr(),
+                    // unused 8x16 slots fetch tile $FF, which lives in the $1000 table
+                    _ => 0x1000,
+                };
+                self.mapper.borrow_mut().ppu_address(sprite_bank);
+            }
+            if self.cycles == 321 {
+                let bknd_bank = self.ctrl.bknd_pattern_addr();
+                self.mapper.borrow_mut().ppu_address(bknd_bank);
+            }
+        }
+
+        if visible && (1..=256).contains(&self.cycles) {
+            if self.rendering_enabled() {
+                self.output_pixel();
+            } else {
+                self.output_blank_pixel();
+            }
+        }
+
+        if self.scanline == 241 && self.cycles == 1 {
+            self.status.set_vblank_status(true);
+            if self.ctrl.generate_vblank_nmi() {
+                self.nmi_interrupt = Some(1);
+            }
+            frame_complete = true;
+
+            self.io_latch_age = self.io_latch_age.saturating_add(1);
+            if self.io_latch_age >= IO_LATCH_DECAY_FRAMES {
+                self.io_latch = 0;
+            }
+        }
+
+        if pre_render && self.cycles == 1 {
+            self.nmi_interrupt = None;
+            self.status.set_sprite_zero_hit_status(false);
+            self.status.set_sprite_overflow_status(false);
+            self.status.reset_vblank_status();
+        }
+
+        self.cycles += 1;
+        // odd frames are one dot shorter while rendering is enabled
+        if pre_render && self.cycles == 340 && self.odd_frame && self.rendering_enabled() {
+            self.cycles = 341;
+        }
+
+        if self.cycles >= 341 {
+            self.cycles = 0;
+            self.scanline += 1;
+
+            if self.scanline > 261 {
+                self.scanline = 0;
+                self.odd_frame = !self.odd_frame;
+            }
+        }
+
+        frame_complete
+    }
+
+    fn fetch_background(&mut self) {
+        let dot = self.cycles;
+
+        if (2..=257).contains(&dot) || (321..=337).contains(&dot) {
+            self.shifters.shift();
+
+            match (dot - 1) % 8 {
+                0 => {
+                    self.shifters.load(
+                        self.next_pattern_lo,
+                        self.next_pattern_hi,
+                        self.next_palette,
+                    );
+                    let tile_addr = self.loopy.tile_addr();
+                    self.next_tile = self.read_nametable(tile_addr);
+                }
+                2 => {
+                    let attr_addr = self.loopy.attribute_addr();
+                    let attr = self.read_nametable(attr_addr);
+                    self.next_palette = (attr >> self.loopy.attribute_shift()) & 0b11;
+                }
+                4 => self.next_pattern_lo = self.read_chr(self.background_pattern_addr()),
+                6 => self.next_pattern_hi = self.read_chr(self.background_pattern_addr() + 8),
+                7 => self.loopy.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        if dot == 256 {
+            self.loopy.increment_y();
+        }
+        if dot == 257 {
+            self.loopy.copy_horizontal();
+        }
+        if self.scanline == 261 && (280..=304).contains(&dot) {
+            self.loopy.copy_vertical();
+        }
+    }
+
+    fn background_pattern_addr(&self) -> u16 {
+        self.ctrl.bknd_pattern_addr() + self.next_tile as u16 * 16 + self.loopy.fine_y()
+    }
+
+    // Copies the first 8 sprites in range of the next scanline into
+    // secondary OAM. Past the eighth sprite the hardware keeps scanning for
+    // the overflow flag, but a bug advances the byte offset along with the
+    // sprite index on every miss, so it compares tile, attribute and x bytes
+    // as if they were y coordinates
+    // (https://www.nesdev.org/wiki/PPU_sprite_evaluation).
+    fn evaluate_sprites(&mut self) {
+        self.secondary_oam_count = 0;
+        self.secondary_has_sprite_zero = false;
+
+        let line = self.scanline as usize;
+        let height = self.ctrl.sprite_size() as usize;
+        let in_range = |y: u8| line.wrapping_sub(y as usize) < height;
+
+        let mut n = 0;
+        while n < 64 && self.secondary_oam_count < 8 {
+            if in_range(self.oam_data[n * 4]) {
+                let slot = self.secondary_oam_count * 4;
+                self.secondary_oam[slot..slot + 4]
+                    .copy_from_slice(&self.oam_data[n * 4..n * 4 + 4]);
+                self.secondary_has_sprite_zero |= n == 0;
+                self.secondary_oam_count += 1;
+            }
+            n += 1;
+        }
+
+        let mut m = 0;
+        while n < 64 {
+            if in_range(self.oam_data[n * 4 + m]) {
+                self.status.set_sprite_overflow_status(true);
+                break;
+            }
+            n += 1;
+            m = (m + 1) % 4;
+        }
+    }
+
+    // Fetches the patterns of the sprites in secondary OAM. The pre-render
+    // line fetches none, so nothing is drawn on line 0.
+    fn fetch_sprites(&mut self) {
+        self.line_sprites.clear();
+        if self.scanline >= 240 {
+            return;
+        }
+
+        let line = self.scanline as usize;
+        let height = self.ctrl.sprite_size() as usize;
+        for i in 0..self.secondary_oam_count {
+            let sprite = &self.secondary_oam[i * 4..i * 4 + 4];
+            let (y, tile, attributes, x) = (sprite[0], sprite[1], sprite[2], sprite[3]);
+
+            let mut row = line.wrapping_sub(y as usize);
+            if attributes & 0b1000_0000 != 0 {
+                row = height - 1 - row;
+            }
+            let tile_addr = if height == 8 {
+                self.ctrl.sprt_pattern_addr() + tile as u16 * 16
+            } else {
+                // 8x16 sprites take the pattern table from bit 0 of the tile
+                // index and stack tile n+1 below tile n
+                let bank = (tile as u16 & 1) * 0x1000;
+                let top = tile as u16 & 0xFE;
+                bank + (top + (row / 8) as u16) * 16
+            };
+            let addr = tile_addr + (row % 8) as u16;
+            let mut pattern_lo = self.read_chr(addr);
+            let mut pattern_hi = self.read_chr(addr + 8);
+            if attributes & 0b0100_0000 != 0 {
+                pattern_lo = pattern_lo.reverse_bits();
+                pattern_hi = pattern_hi.reverse_bits();
+            }
+
+            self.line_sprites.push(SpriteSlot {
+                x,
+                attributes,
+                pattern_lo,
+                pattern_hi,
+                sprite_zero: i == 0 && self.secondary_has_sprite_zero,
+            });
+        }
+    }
+
+    fn output_pixel(&mut self) {
+        let x = self.cycles - 1;
+
+        let (bg_pixel, bg_palette) =
+            if self.mask.show_background() && (x >= 8 || self.mask.leftmost_8pxl_background()) {
+                self.shifters.pixel(self.loopy.fine_x())
+            } else {
+                (0, 0)
+            };
+
+        if self.is_sprite_0_hit(x, bg_pixel) {
+            self.status.set_sprite_zero_hit_status(true);
+        }
+
+        // the first opaque sprite in OAM order wins, even if it is behind
+        // the background and a later sprite is not
+        let sprite = if self.mask.show_sprites() && (x >= 8 || self.mask.leftmost_8pxl_sprite()) {
+            self.line_sprites
+                .iter()
+                .map(|sprite| (sprite.pixel(x), sprite))
+                .find(|&(pixel, _)| pixel != 0)
+        } else {
+            None
+        };
+
+        let palette_index = match sprite {
+            Some((pixel, sprite)) if bg_pixel == 0 || !sprite.behind_background() => {
+                0x10 + sprite.palette() * 4 + pixel
+            }
+            _ if bg_pixel != 0 => bg_palette * 4 + bg_pixel,
+            _ => 0,
+        };
+
+        self.put_pixel(x, palette_index);
+    }
+
+    // With rendering disabled the screen shows the backdrop, or the palette
+    // entry v points at when it is inside the palette.
+    fn output_blank_pixel(&mut self) {
+        let addr = self.loopy.vram_addr();
+        let palette_index = if addr >= 0x3F00 {
+            (addr & 0x1F) as u8
+        } else {
+            0
+        };
+        self.put_pixel(self.cycles - 1, palette_index);
+    }
+
+    // $3F10/$3F14/$3F18/$3F1C mirror the background entries below them
+    fn mirror_palette_index(index: u8) -> usize {
+        let index = index & 0x1F;
+        if index & 0x13 == 0x10 {
+            (index & 0x0F) as usize
+        } else {
+            index as usize
+        }
+    }
+
+    fn put_pixel(&mut self, x: usize, palette_index: u8) {
+        let mut color = self.palette_table[Self::mirror_palette_index(palette_index)] & 0x3F;
+        if self.mask.is_grayscale() {
+            color &= 0x30;
+        }
+        let rgb = palette::emphasise(
+            palette::SYSTEM_PALLETE[color as usize],
+            self.mask.contains(MaskRegister::EMPHASIZE_RED),
+            self.mask.contains(MaskRegister::EMPHASIZE_GREEN),
+            self.mask.contains(MaskRegister::EMPHASIZE_BLUE),
+        );
+        self.frame.set_pixel(x, self.scanline as usize, rgb);
+    }
+
+    fn rendering_enabled(&self) -> bool {
+        self.mask.show_background() || self.mask.show_sprites()
+    }
+
+    fn is_rendering(&self) -> bool {
+        (self.scanline < 240 || self.scanline == 261) && self.rendering_enabled()
+    }
+
+    // Sprite 0 hits where an opaque sprite 0 pixel overlaps an opaque
+    // background pixel, whatever their priority. It never hits at x = 255,
+    // nor in the left 8 pixels while either layer is clipped there.
+    fn is_sprite_0_hit(&self, x: usize, bg_pixel: u8) -> bool {
+        if bg_pixel == 0 || !self.mask.show_sprites() || x == 255 {
+            return false;
+        }
+        if x < 8 && !(self.mask.leftmost_8pxl_background() && self.mask.leftmost_8pxl_sprite()) {
+            return false;
+        }
+        self.line_sprites
+            .first()
+            .is_some_and(|sprite| sprite.sprite_zero && sprite.pixel(x) != 0)
+    }
+
+    // the picture drawn so far; complete once tick reports the end of a frame
+    pub fn frame(&self) -> &Frame {
+        &self.frame
+    }
+
+    pub fn new_empty_rom() -> Self {
+        let rom = test_rom_with(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::HORIZONTAL);
+        NesPPU::new(Rc::new(RefCell::new(Nrom::new(rom))))
+    }
+
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.borrow().mirroring()
+    }
+
+    pub fn read_chr(&self, addr: u16) -> u8 {
+        self.mapper.borrow().read_chr(addr)
+    }
+
+    pub fn read_tile(&self, addr: u16) -> [u8; 16] {
+        let mut tile = [0; 16];
+        for (i, byte) in tile.iter_mut().enumerate() {
+            *byte = self.read_chr(addr + i as u16);
+        }
+        tile
+    }
+
+    pub fn write_to_ppu_addr(&mut self, value: u8) {
+        self.loopy.write_addr(value);
+        if !self.loopy.write_toggle() {
+            self.put_vram_addr_on_bus();
+        }
+    }
+
+    pub fn write_to_ctrl(&mut self, value: u8) {
+        let before_nmi_status = self.ctrl.generate_vblank_nmi();
+        self.ctrl.update(value);
+        self.loopy.write_ctrl(value);
+        if !before_nmi_status && self.ctrl.generate_vblank_nmi() && self.status.is_in_vblank() {
+            self.nmi_interrupt = Some(1);
+        }
+    }
+
+    pub fn write_to_data(&mut self, value: u8) {
+        let addr = self.loopy.vram_addr();
+        self.put_vram_addr_on_bus();
+        match addr {
+            0..=0x1fff => self.mapper.borrow_mut().write_chr(addr, value),
+            0x2000..=0x3eff => self.write_nametable(addr, value),
+            _ => self.palette_table[Self::mirror_palette_index(addr as u8)] = value,
+        }
+        self.increment_vram_addr();
+    }
+
+    pub fn read_data(&mut self) -> u8 {
+        let addr = self.loopy.vram_addr();
+        self.put_vram_addr_on_bus();
+        self.increment_vram_addr();
+
+        match addr {
+            0..=0x1fff => {
+                let result = self.internal_data_buf;
+                self.internal_data_buf = self.read_chr(addr);
+                result
+            }
+            0x2000..=0x3eff => {
+                let result = self.internal_data_buf;
+                self.internal_data_buf = self.read_nametable(addr);
+                result
+            }
+            _ => {
+                // palette reads are not buffered, but the buffer still picks
+                // up the nametable byte underneath
+                self.internal_data_buf = self.read_nametable(addr);
+                self.palette_table[Self::mirror_palette_index(addr as u8)]
+            }
+        }
+    }
+
+    // Pages 0 and 1 are the console's own RAM, 2 and 3 live on the cartridge
+    fn read_nametable(&self, addr: u16) -> u8 {
+        match self.mirror_vram_addr(addr) {
+            addr @ 0..=0x07FF => self.vram[addr as usize],
+            addr => self.mapper.borrow().read_nametable(addr - 0x800),
+        }
+    }
+
+    fn write_nametable(&mut self, addr: u16, value: u8) {
+        match self.mirror_vram_addr(addr) {
+            addr @ 0..=0x07FF => self.vram[addr as usize] = value,
+            addr => self
+                .mapper
+                .borrow_mut()
+                .write_nametable(addr - 0x800, value),
+        }
+    }
+
+    // Maps $2000-$2FFF (and its $3000-$3EFF mirror) to a 1KB page of vram,
+    // 2000 2400 / 2800 2C00 laid out as:
+    //
+    //   Horizontal   Vertical    Single A    Single B    Four-screen
+    //   [ 0 ][ 0 ]   [ 0 ][ 1 ]  [ 0 ][ 0 ]  [ 1 ][ 1 ]  [ 0 ][ 1 ]
+    //   [ 1 ][ 1 ]   [ 0 ][ 1 ]  [ 0 ][ 0 ]  [ 1 ][ 1 ]  [ 2 ][ 3 ]
+    pub fn mirror_vram_addr(&self, addr: u16) -> u16 {
+        let vram_index = addr & 0x0FFF;
+        let name_table = vram_index / 0x400;
+        let page = match self.mirroring() {
+            Mirroring::HORIZONTAL => name_table / 2,
+            Mirroring::VERTICAL => name_table % 2,
+            Mirroring::SINGLE_SCREEN_LOWER => 0,
+            Mirroring::SINGLE_SCREEN_UPPER => 1,
+            Mirroring::FOUR_SCREEN => name_table,
+        };
+        page * 0x400 + vram_index % 0x400
+    }
+
+    pub fn read_status(&mut self) -> u8 {
+        let data = self.status.snapshot();
+        self.status.reset_vblank_status();
+        self.loopy.reset_latch();
+        data
+    }
+
+    pub fn write_to_oam_addr(&mut self, value: u8) {
+        self.oam_addr = value;
+    }
+
+    pub fn write_to_oam_data(&mut self, value: u8) {
+        self.oam_data[self.oam_addr as usize] = value;
+        self.increment_oam_addr();
+    }
+
+    pub fn read_oam_data(&mut self) -> u8 {
+        self.oam_data[self.oam_addr as usize]
+    }
+
+    pub fn write_to_scroll(&mut self, data: u8) {
+        self.loopy.write_scroll(data);
+    }
+
+    pub fn write_to_mask(&mut self, data: u8) {
+        self.mask.update(data);
+    }
+
+    // CPU access to $2000-$2007. Every access drives the PPU data bus, so
+    // bits the register does not produce come from the I/O latch.
+    pub fn read_register(&mut self, addr: u16) -> u8 {
+        let data = match addr & 0x7 {
+            2 => (self.read_status() & 0xE0) | (self.io_latch & 0x1F),
+            4 => self.read_oam_data(),
+            7 if self.loopy.vram_addr() >= 0x3F00 => {
+                (self.read_data() & 0x3F) | (self.io_latch & 0xC0)
+            }
+            7 => self.read_data(),
+            _ => self.io_latch,
+        };
+        self.refresh_io_latch(data);
+        data
+    }
+
+    pub fn write_register(&mut self, addr: u16, data: u8) {
+        self.refresh_io_latch(data);
+        match addr & 0x7 {
+            0 => self.write_to_ctrl(data),
+            1 => self.write_to_mask(data),
+            2 => {} // PPUSTATUS is read-only
+            3 => self.write_to_oam_addr(data),
+            4 => self.write_to_oam_data(data),
+            5 => self.write_to_scroll(data),
+            6 => self.write_to_ppu_addr(data),
+            _ => self.write_to_data(data),
+        }
+    }
+
+    fn refresh_io_latch(&mut self, data: u8) {
+        self.io_latch = data;
+        self.io_latch_age = 0;
+    }
+}
+
+impl Snapshot for NesPPU {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_bytes(&self.palette_table);
+        writer.write_bytes(&self.vram);
+        writer.write_u8(self.ctrl.bits());
+        writer.write_u8(self.status.bits());
+        writer.write_u8(self.mask.bits());
+        self.loopy.save_state(writer);
+        writer.write_bytes(&self.oam_data);
+        writer.write_u8(self.oam_addr);
+        writer.write_bool(self.nmi_interrupt.is_some());
+        writer.write_u16(self.scanline);
+        writer.write_u16(self.cycles as u16);
+        writer.write_u8(self.internal_data_buf);
+        writer.write_u8(self.io_latch);
+        writer.write_u8(self.io_latch_age);
+        writer.write_bool(self.odd_frame);
+        writer.write_u8(self.next_tile);
+        writer.write_u8(self.next_palette);
+        writer.write_u8(self.next_pattern_lo);
+        writer.write_u8(self.next_pattern_hi);
+        self.shifters.save_state(writer);
+        writer.write_bytes(&self.secondary_oam);
+        writer.write_u8(self.secondary_oam_count as u8);
+        writer.write_bool(self.secondary_has_sprite_zero);
+        writer.write_u8(self.line_sprites.len() as u8);
+        for sprite in &self.line_sprites {
+            sprite.save_state(writer);
+        }
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        reader.read_bytes(&mut self.palette_table)?;
+        reader.read_bytes(&mut self.vram)?;
+        self.ctrl = ControlRegister::from_bits_retain(reader.read_u8()?);
+        self.status = StatusRegister::from_bits_retain(reader.read_u8()?);
+        self.mask = MaskRegister::from_bits_retain(reader.read_u8()?);
+        self.loopy.load_state(reader)?;
+        reader.read_bytes(&mut self.oam_data)?;
+        self.oam_addr = reader.read_u8()?;
+        self.nmi_interrupt = if reader.read_bool()? { Some(1) } else { None };
+        self.scanline = reader.read_u16()?;
+        self.cycles = reader.read_u16()? as usize;
+        self.internal_data_buf = reader.read_u8()?;
+        self.io_latch = reader.read_u8()?;
+        self.io_latch_age = reader.read_u8()?;
+        self.odd_frame = reader.read_bool()?;
+        self.next_tile = reader.read_u8()?;
+        self.next_palette = reader.read_u8()?;
+        self.next_pattern_lo = reader.read_u8()?;
+        self.next_pattern_hi = reader.read_u8()?;
+        self.shifters.load_state(reader)?;
+        reader.read_bytes(&mut self.secondary_oam)?;
+        self.secondary_oam_count = (reader.read_u8()? as usize).min(8);
+        self.secondary_has_sprite_zero = reader.read_bool()?;
+        let sprite_count = reader.read_u8()?;
+        self.line_sprites.clear();
+        for _ in 0..sprite_count {
+            let mut sprite = SpriteSlot {
+                x: 0,
+                attributes: 0,
+                pattern_lo: 0,
+                pattern_hi: 0,
+                sprite_zero: false,
+            };
+            sprite.load_state(reader)?;
+            self.line_sprites.push(sprite);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mappers::mmc3::Mmc3;
+
+    // tile 1 is solid color 1, tile 2 solid color 3
+    fn ppu_with_tiles() -> NesPPU {
+        let mut chr_rom = vec![0; 0x2000];
+        chr_rom[0x10..0x18].copy_from_slice(&[0xFF; 8]);
+        chr_rom[0x20..0x30].copy_from_slice(&[0xFF; 16]);
+        // in the $1000 table, tile 2 is solid color 1 and tile 3 solid color 2
+        chr_rom[0x1020..0x1028].copy_from_slice(&[0xFF; 8]);
+        chr_rom[0x1038..0x1040].copy_from_slice(&[0xFF; 8]);
+        let rom = test_rom_with(vec![0; 0x4000], chr_rom, 0, Mirroring::HORIZONTAL);
+        let mut ppu = NesPPU::new(Rc::new(RefCell::new(Nrom::new(rom))));
+        ppu.palette_table[0] = 0x0F;
+        ppu.palette_table[1] = 0x30;
+        ppu.palette_table[0x13] = 0x16;
+        ppu
+    }
+
+    fn run_until_line(ppu: &mut NesPPU, line: u16) {
+        while ppu.scanline != line {
+            ppu.tick(1);
+        }
+    }
+
+    fn pixel(ppu: &NesPPU, x: usize, y: usize) -> (u8, u8, u8) {
+        let base = (y * 256 + x) * 3;
+        let data = &ppu.frame().data;
+        (data[base], data[base + 1], data[base + 2])
+    }
+
+    #[test]
+    fn test_mid_frame_scroll_split() {
+        let mut ppu = ppu_with_tiles();
+        // the left half of the first nametable is solid
+        for row in 0..30 {
+            for column in 0..16 {
+                ppu.vram[row * 32 + column] = 1;
+            }
+        }
+        ppu.write_to_mask(0b0000_1010);
+        run_until_line(&mut ppu, 241);
+
+        run_until_line(&mut ppu, 100);
+        ppu.write_to_scroll(64);
+        ppu.write_to_scroll(0);
+        run_until_line(&mut ppu, 241);
+
+        let white = palette::SYSTEM_PALLETE[0x30];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 127, 50), white);
+        assert_eq!(pixel(&ppu, 128, 50), black);
+        assert_eq!(pixel(&ppu, 63, 150), white);
+        assert_eq!(pixel(&ppu, 64, 150), black);
+    }
+
+    #[test]
+    fn test_mid_frame_scroll_via_ppuaddr() {
+        let mut ppu = ppu_with_tiles();
+        for row in 0..30 {
+            for column in 0..16 {
+                ppu.vram[row * 32 + column] = 1;
+            }
+        }
+        ppu.write_to_mask(0b0000_1010);
+        run_until_line(&mut ppu, 241);
+
+        // the second write lands in v at once: coarse x 8, top row
+        run_until_line(&mut ppu, 100);
+        ppu.write_to_ppu_addr(0x00);
+        ppu.write_to_ppu_addr(0x08);
+        run_until_line(&mut ppu, 241);
+
+        let white = palette::SYSTEM_PALLETE[0x30];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 127, 50), white);
+        assert_eq!(pixel(&ppu, 128, 50), black);
+        assert_eq!(pixel(&ppu, 63, 150), white);
+        assert_eq!(pixel(&ppu, 64, 150), black);
+    }
+
+    fn run_frame(ppu: &mut NesPPU) {
+        run_until_line(ppu, 0);
+        run_until_line(ppu, 241);
+    }
+
+    fn sprite_ppu(mask: u8) -> NesPPU {
+        let mut ppu = ppu_with_tiles();
+        ppu.oam_data = [0xFF; 256];
+        ppu.palette_table[0x11] = 0x2A;
+        ppu.palette_table[0x12] = 0x12;
+        ppu.write_to_mask(mask);
+        ppu
+    }
+
+    #[test]
+    fn test_eight_sprites_per_scanline() {
+        let mut ppu = sprite_ppu(0b0001_0100);
+        for i in 0..9 {
+            ppu.oam_data[i * 4..i * 4 + 4].copy_from_slice(&[20, 2, 0, i as u8 * 10]);
+        }
+        run_frame(&mut ppu);
+
+        let red = palette::SYSTEM_PALLETE[0x16];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 70, 21), red);
+        assert_eq!(pixel(&ppu, 80, 21), black);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW_FLAG));
+    }
+
+    #[test]
+    fn test_sprite_overflow_hardware_bug() {
+        let mut ppu = sprite_ppu(0b0001_0100);
+        for i in 0..8 {
+            ppu.oam_data[i * 4..i * 4 + 4].copy_from_slice(&[20, 2, 0, i as u8 * 10]);
+        }
+        run_frame(&mut ppu);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_OVERFLOW_FLAG));
+
+        // after 8 hits the scan reads sprite 9's tile byte as a y coordinate
+        ppu.oam_data[9 * 4 + 1] = 22;
+        run_frame(&mut ppu);
+        assert!(ppu.status.contains(StatusRegister::SPRITE_OVERFLOW_FLAG));
+
+        // and skips the y byte of sprite 10, which really is on the line
+        ppu.oam_data[9 * 4 + 1] = 2;
+        ppu.oam_data[10 * 4] = 20;
+        run_frame(&mut ppu);
+        assert!(!ppu.status.contains(StatusRegister::SPRITE_OVERFLOW_FLAG));
+    }
+
+    #[test]
+    fn test_8x16_sprites() {
+        let mut ppu = sprite_ppu(0b0001_0100);
+        ppu.write_to_ctrl(0b0010_0000);
+        // odd tile index: pattern table $1000, tiles 2 and 3
+        ppu.oam_data[0..4].copy_from_slice(&[40, 0x03, 0, 10]);
+        ppu.oam_data[4..8].copy_from_slice(&[40, 0x03, 0b1000_0000, 20]);
+        run_frame(&mut ppu);
+
+        let green = palette::SYSTEM_PALLETE[0x2A];
+        let blue = palette::SYSTEM_PALLETE[0x12];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 10, 41), green);
+        assert_eq!(pixel(&ppu, 10, 48), green);
+        assert_eq!(pixel(&ppu, 10, 49), blue);
+        assert_eq!(pixel(&ppu, 10, 56), blue);
+        assert_eq!(pixel(&ppu, 10, 57), black);
+        // flipped vertically over all 16 rows
+        assert_eq!(pixel(&ppu, 20, 41), blue);
+        assert_eq!(pixel(&ppu, 20, 56), green);
+    }
+
+    #[test]
+    fn test_sprite_background_priority() {
+        let mut ppu = sprite_ppu(0b0001_1110);
+        for row in 0..30 {
+            ppu.vram[row * 32] = 1;
+        }
+        // sprite 0 is behind the background, sprite 1 in front at the same spot
+        ppu.oam_data[0..4].copy_from_slice(&[20, 2, 0b0010_0000, 4]);
+        ppu.oam_data[4..8].copy_from_slice(&[20, 1, 0b0000_0000, 4]);
+        ppu.oam_data[8..12].copy_from_slice(&[60, 2, 0b0010_0000, 4]);
+        run_frame(&mut ppu);
+
+        let white = palette::SYSTEM_PALLETE[0x30];
+        let red = palette::SYSTEM_PALLETE[0x16];
+        let green = palette::SYSTEM_PALLETE[0x2A];
+        // the front-most opaque sprite is behind, so the background wins
+        assert_eq!(pixel(&ppu, 5, 21), white);
+        assert_eq!(pixel(&ppu, 9, 21), red);
+        // over a transparent background a behind sprite still shows
+        assert_eq!(pixel(&ppu, 9, 61), red);
+        assert_eq!(pixel(&ppu, 5, 61), white);
+        assert_ne!(pixel(&ppu, 9, 21), green);
+    }
+
+    fn run_until_dot(ppu: &mut NesPPU, line: u16, dot: usize) {
+        run_until_line(ppu, line);
+        while ppu.cycles != dot {
+            ppu.tick(1);
+        }
+    }
+
+    fn sprite_zero_hit(ppu: &NesPPU) -> bool {
+        ppu.status.contains(StatusRegister::SPRITE_ZERO_HIT_FLAG)
+    }
+
+    // a solid background on the left half, with sprite 0 placed by the test
+    fn sprite_zero_ppu(sprite: [u8; 4], mask: u8) -> NesPPU {
+        let mut ppu = sprite_ppu(mask);
+        for row in 0..30 {
+            for column in 0..16 {
+                ppu.vram[row * 32 + column] = 1;
+            }
+        }
+        ppu.oam_data[0..4].copy_from_slice(&sprite);
+        ppu
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_at_first_overlapping_pixel() {
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 100], 0b0001_1110);
+
+        run_until_dot(&mut ppu, 51, 0);
+        assert!(!sprite_zero_hit(&ppu));
+        // pixel x is output at dot x + 1
+        run_until_dot(&mut ppu, 51, 101);
+        assert!(!sprite_zero_hit(&ppu));
+        ppu.tick(1);
+        assert!(sprite_zero_hit(&ppu));
+
+        // cleared on the pre-render line
+        run_until_dot(&mut ppu, 261, 2);
+        assert!(!sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_needs_opaque_background() {
+        // the right half of the nametable is empty
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 200], 0b0001_1110);
+        run_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        // a sprite behind the background still hits
+        let mut ppu = sprite_zero_ppu([50, 2, 0b0010_0000, 100], 0b0001_1110);
+        run_frame(&mut ppu);
+        assert!(sprite_zero_hit(&ppu));
+
+        // not with the background disabled
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 100], 0b0001_0110);
+        run_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    fn test_sprite_zero_hit_respects_left_clipping() {
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 0], 0b0001_1110);
+        run_frame(&mut ppu);
+        assert!(sprite_zero_hit(&ppu));
+
+        // with either layer clipped, only pixels 8 and up can hit
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 0], 0b0001_1010);
+        run_frame(&mut ppu);
+        assert!(!sprite_zero_hit(&ppu));
+
+        let mut ppu = sprite_zero_ppu([50, 2, 0, 1], 0b0001_1100);
+        run_until_dot(&mut ppu, 51, 9);
+        assert!(!sprite_zero_hit(&ppu));
+        ppu.tick(1);
+        assert!(sprite_zero_hit(&ppu));
+    }
+
+    // a solid color 1 background everywhere
+    fn solid_background_ppu(mask: u8) -> NesPPU {
+        let mut ppu = sprite_ppu(mask);
+        for tile in ppu.vram[0..0x3C0].iter_mut() {
+            *tile = 1;
+        }
+        ppu
+    }
+
+    #[test]
+    fn test_left_column_clipping() {
+        let mut ppu = solid_background_ppu(0b0000_1000);
+        run_frame(&mut ppu);
+        let white = palette::SYSTEM_PALLETE[0x30];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 7, 10), black);
+        assert_eq!(pixel(&ppu, 8, 10), white);
+
+        let mut ppu = solid_background_ppu(0b0001_1010);
+        ppu.oam_data[0..4].copy_from_slice(&[20, 2, 0, 4]);
+        run_frame(&mut ppu);
+        let red = palette::SYSTEM_PALLETE[0x16];
+        assert_eq!(pixel(&ppu, 7, 21), white);
+        assert_eq!(pixel(&ppu, 8, 21), red);
+    }
+
+    #[test]
+    fn test_greyscale() {
+        let mut ppu = solid_background_ppu(0b0000_1011);
+        ppu.palette_table[1] = 0x16;
+        run_frame(&mut ppu);
+        assert_eq!(pixel(&ppu, 10, 10), palette::SYSTEM_PALLETE[0x10]);
+    }
+
+    #[test]
+    fn test_rendering_disabled_shows_backdrop() {
+        let mut ppu = solid_background_ppu(0);
+        run_frame(&mut ppu);
+        assert_eq!(pixel(&ppu, 10, 10), palette::SYSTEM_PALLETE[0x0F]);
+
+        // unless v points into the palette
+        ppu.write_to_ppu_addr(0x3F);
+        ppu.write_to_ppu_addr(0x01);
+        run_frame(&mut ppu);
+        assert_eq!(pixel(&ppu, 10, 10), palette::SYSTEM_PALLETE[0x30]);
+    }
+
+    #[test]
+    fn test_color_emphasis() {
+        let mut ppu = solid_background_ppu(0b0010_1010);
+        ppu.palette_table[1] = 0x20;
+        run_frame(&mut ppu);
+        let (r, g, b) = palette::SYSTEM_PALLETE[0x20];
+        assert_eq!(
+            pixel(&ppu, 10, 10),
+            (r, (g as u16 * 3 / 4) as u8, (b as u16 * 3 / 4) as u8)
+        );
+
+        ppu.write_to_mask(0b1110_1010);
+        run_frame(&mut ppu);
+        let quarter_off = |c: u8| (c as u16 * 3 / 4) as u8;
+        assert_eq!(
+            pixel(&ppu, 10, 10),
+            (quarter_off(r), quarter_off(g), quarter_off(b))
+        );
+    }
+
+    fn ppu_with_mirroring(mirroring: Mirroring) -> NesPPU {
+        let rom = test_rom_with(vec![0; 0x4000], vec![0; 0x2000], 0, mirroring);
+        NesPPU::new(Rc::new(RefCell::new(Nrom::new(rom))))
+    }
+
+    #[test]
+    fn test_nametable_mirroring() {
+        let cases = [
+            (Mirroring::HORIZONTAL, [0x000, 0x000, 0x400, 0x400]),
+            (Mirroring::VERTICAL, [0x000, 0x400, 0x000, 0x400]),
+            (Mirroring::SINGLE_SCREEN_LOWER, [0x000, 0x000, 0x000, 0x000]),
+            (Mirroring::SINGLE_SCREEN_UPPER, [0x400, 0x400, 0x400, 0x400]),
+            (Mirroring::FOUR_SCREEN, [0x000, 0x400, 0x800, 0xC00]),
+        ];
+        for (mirroring, pages) in cases {
+            let ppu = ppu_with_mirroring(mirroring);
+            for (name_table, page) in pages.iter().enumerate() {
+                let base = 0x2000 + name_table as u16 * 0x400;
+                for offset in [0x000, 0x123, 0x3FF] {
+                    assert_eq!(
+                        ppu.mirror_vram_addr(base + offset),
+                        page + offset,
+                        "{:?} at {:04X}",
+                        mirroring,
+                        base + offset
+                    );
+                    // $3000-$3EFF mirrors $2000-$2EFF
+                    if base + offset < 0x2F00 {
+                        assert_eq!(ppu.mirror_vram_addr(base + offset + 0x1000), page + offset);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_four_screen_nametables_are_distinct() {
+        let mut ppu = ppu_with_mirroring(Mirroring::FOUR_SCREEN);
+        for name_table in 0..4u8 {
+            ppu.write_to_ppu_addr(0x20 + name_table * 4);
+            ppu.write_to_ppu_addr(0x05);
+            ppu.write_to_data(0x10 + name_table);
+        }
+        for name_table in 0..4u8 {
+            ppu.write_to_ppu_addr(0x20 + name_table * 4);
+            ppu.write_to_ppu_addr(0x05);
+            ppu.read_data(); // buffered
+            assert_eq!(ppu.read_data(), 0x10 + name_table);
+        }
+        // the third and fourth nametables are the cartridge's RAM
+        assert_eq!(ppu.mapper.borrow().read_nametable(0x005), 0x12);
+        assert_eq!(ppu.mapper.borrow().read_nametable(0x405), 0x13);
+    }
+
+    #[test]
+    fn test_write_only_registers_read_the_io_latch() {
+        let mut ppu = NesPPU::new_empty_rom();
+        ppu.write_register(0x2003, 0x5A);
+        assert_eq!(ppu.read_register(0x2000), 0x5A);
+        assert_eq!(ppu.read_register(0x2006), 0x5A);
+
+        // PPUSTATUS only drives its top 3 bits
+        ppu.write_register(0x2002, 0x1F);
+        ppu.status.set_vblank_status(true);
+        assert_eq!(ppu.read_register(0x2002), 0x9F);
+        assert_eq!(ppu.read_register(0x2005), 0x9F);
+
+        // and the latch fades after a while
+        for _ in 0..IO_LATCH_DECAY_FRAMES {
+            while !ppu.tick(1) {}
+        }
+        assert_eq!(ppu.read_register(0x2001), 0);
+    }
+
+    #[test]
+    fn test_palette_reads_and_high_vram_mirror() {
+        let mut ppu = ppu_with_mirroring(Mirroring::VERTICAL);
+        ppu.vram[0x305] = 0x66;
+        ppu.palette_table[0x01] = 0x21;
+
+        // $3000-$3EFF reads the nametables, buffered
+        ppu.write_register(0x2006, 0x33);
+        ppu.write_register(0x2006, 0x05);
+        ppu.read_register(0x2007);
+        assert_eq!(ppu.read_register(0x2007), 0x66);
+
+        // palette reads are immediate, with the top two bits from the latch
+        ppu.write_register(0x2006, 0xFF);
+        ppu.write_register(0x2006, 0x21);
+        ppu.write_register(0x2003, 0xFF);
+        assert_eq!(ppu.read_register(0x2007), 0xC0 | 0x21);
+
+        // $3F10 mirrors $3F00
+        ppu.write_register(0x2006, 0x3F);
+        ppu.write_register(0x2006, 0x10);
+        ppu.write_register(0x2007, 0x0F);
+        assert_eq!(ppu.palette_table[0x00], 0x0F);
+    }
+
+    #[test]
+    fn test_sprite_drawn_one_line_below_oam_y() {
+        let mut ppu = ppu_with_tiles();
+        ppu.oam_data[0..4].copy_from_slice(&[20, 2, 0b0000_0000, 30]);
+        ppu.write_to_mask(0b0001_0100);
+        run_until_line(&mut ppu, 241);
+        run_until_line(&mut ppu, 241 + 1);
+        run_until_line(&mut ppu, 241);
+
+        let red = palette::SYSTEM_PALLETE[0x16];
+        let black = palette::SYSTEM_PALLETE[0x0F];
+        assert_eq!(pixel(&ppu, 30, 20), black);
+        assert_eq!(pixel(&ppu, 30, 21), red);
+        assert_eq!(pixel(&ppu, 37, 28), red);
+        assert_eq!(pixel(&ppu, 38, 28), black);
+        assert_eq!(pixel(&ppu, 30, 29), black);
+    }
+
+    #[test]
+    fn test_ppuaddr_writes_clock_mmc3_irq() {
+        let rom = test_rom_with(vec![0; 0x8000], vec![0; 0x2000], 4, Mirroring::VERTICAL);
+        let mapper = Rc::new(RefCell::new(Mmc3::new(rom)));
+        let mut ppu = NesPPU::new(mapper.clone());
+        mapper.borrow_mut().write_prg(0xC000, 2);
+        mapper.borrow_mut().write_prg(0xE001, 0);
+
+        // rendering is off, so only $2006 moves A12
+        for _ in 0..2 {
+            ppu.write_to_ppu_addr(0x00);
+            ppu.write_to_ppu_addr(0x00);
+            ppu.write_to_ppu_addr(0x10);
+            ppu.write_to_ppu_addr(0x00);
+        }
+        assert!(!mapper.borrow().irq_pending());
+        ppu.write_to_ppu_addr(0x00);
+        ppu.write_to_ppu_addr(0x00);
+        // the first write alone does not reach the bus
+        ppu.write_to_ppu_addr(0x10);
+        assert!(!mapper.borrow().irq_pending());
+        ppu.write_to_ppu_addr(0x00);
+        assert!(mapper.borrow().irq_pending());
+    }
+
+    #[test]
+    fn test_ppudata_access_puts_address_on_bus() {
+        let rom = test_rom_with(vec![0; 0x8000], vec![0; 0x2000], 4, Mirroring::VERTICAL);
+        let mapper = Rc::new(RefCell::new(Mmc3::new(rom)));
+        let mut ppu = NesPPU::new(mapper.clone());
+        mapper.borrow_mut().write_prg(0xC000, 0);
+        mapper.borrow_mut().write_prg(0xE001, 0);
+
+        ppu.write_to_ppu_addr(0x0F);
+        ppu.write_to_ppu_addr(0xFF);
+        assert!(!mapper.borrow().irq_pending());
+        // incrementing from $0FFF crosses into the $1000 table
+        ppu.read_data();
+        assert!(mapper.borrow().irq_pending());
+    }
+}
@@ -68,6 +68,14 @@ impl ControlRegister {
         }
     }
 
+    pub fn sprite_size(&self) -> u8 {
+        if !self.contains(ControlRegister::SPRITE_SIZE) {
+            8
+        } else {
+            16
+        }
+    }
+
     pub fn nametable_addr(&self) -> u16 {
         match self.bits() & 0b11 {
             0 => 0x2000,
@@ -0,0 +1,241 @@
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+// The PPU's internal scroll registers, shared by PPUCTRL, PPUSCROLL and
+// PPUADDR (https://www.nesdev.org/wiki/PPU_scrolling).
+//
+// v and t are laid out as 0yyy NNYY YYYX XXXX:
+//   yyy   fine Y scroll
+//   NN    nametable select
+//   YYYYY coarse Y scroll
+//   XXXXX coarse X scroll
+//
+// v is the current VRAM address, t the address the next frame or scanline
+// starts from, x the fine X scroll and w the first/second write toggle.
+pub struct LoopyRegister {
+    v: u16,
+    t: u16,
+    x: u8,
+    w: bool,
+}
+
+impl Default for LoopyRegister {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LoopyRegister {
+    pub fn new() -> Self {
+        LoopyRegister {
+            v: 0,
+            t: 0,
+            x: 0,
+            w: false,
+        }
+    }
+
+    // $2000 write
+    pub fn write_ctrl(&mut self, data: u8) {
+        self.t = (self.t & !0x0C00) | ((data as u16 & 0b11) << 10);
+    }
+
+    // $2005 write
+    pub fn write_scroll(&mut self, data: u8) {
+        if !self.w {
+            self.t = (self.t & !0x001F) | (data as u16 >> 3);
+            self.x = data & 0b111;
+        } else {
+            self.t = (self.t & !0x73E0) | ((data as u16 & 0b111) << 12) | ((data as u16 >> 3) << 5);
+        }
+        self.w = !self.w;
+    }
+
+    // $2006 write; the second write copies t into v
+    pub fn write_addr(&mut self, data: u8) {
+        if !self.w {
+            // bit 14 of t is cleared by the first write
+            self.t = (self.t & 0x00FF) | ((data as u16 & 0x3F) << 8);
+        } else {
+            self.t = (self.t & 0xFF00) | data as u16;
+            self.v = self.t;
+        }
+        self.w = !self.w;
+    }
+
+    // true between the two writes of a $2005/$2006 pair
+    pub fn write_toggle(&self) -> bool {
+        self.w
+    }
+
+    // $2002 read
+    pub fn reset_latch(&mut self) {
+        self.w = false;
+    }
+
+    pub fn vram_addr(&self) -> u16 {
+        self.v & 0x3FFF
+    }
+
+    // after a $2007 access outside of rendering
+    pub fn increment(&mut self, inc: u8) {
+        self.v = self.v.wrapping_add(inc as u16) & 0x7FFF;
+    }
+
+    pub fn fine_x(&self) -> u8 {
+        self.x
+    }
+
+    pub fn fine_y(&self) -> u16 {
+        (self.v >> 12) & 0b111
+    }
+
+    pub fn tile_addr(&self) -> u16 {
+        0x2000 | (self.v & 0x0FFF)
+    }
+
+    pub fn attribute_addr(&self) -> u16 {
+        0x23C0 | (self.v & 0x0C00) | ((self.v >> 4) & 0x38) | ((self.v >> 2) & 0x07)
+    }
+
+    // each attribute byte covers 4x4 tiles, two bits per 2x2 quadrant
+    pub fn attribute_shift(&self) -> u8 {
+        (((self.v >> 4) & 0b100) | (self.v & 0b10)) as u8
+    }
+
+    pub fn increment_coarse_x(&mut self) {
+        if self.v & 0x001F == 31 {
+            self.v &= !0x001F;
+            self.v ^= 0x0400; // next horizontal nametable
+        } else {
+            self.v += 1;
+        }
+    }
+
+    pub fn increment_y(&mut self) {
+        if self.v & 0x7000 != 0x7000 {
+            self.v += 0x1000;
+            return;
+        }
+
+        self.v &= !0x7000;
+        let mut coarse_y = (self.v & 0x03E0) >> 5;
+        if coarse_y == 29 {
+            coarse_y = 0;
+            self.v ^= 0x0800; // next vertical nametable
+        } else if coarse_y == 31 {
+            // rows 30 and 31 hold attributes; wrap without switching nametable
+            coarse_y = 0;
+        } else {
+            coarse_y += 1;
+        }
+        self.v = (self.v & !0x03E0) | (coarse_y << 5);
+    }
+
+    // dot 257: coarse x and the horizontal nametable
+    pub fn copy_horizontal(&mut self) {
+        self.v = (self.v & !0x041F) | (self.t & 0x041F);
+    }
+
+    // pre-render dots 280-304: fine y, coarse y and the vertical nametable
+    pub fn copy_vertical(&mut self) {
+        self.v = (self.v & !0x7BE0) | (self.t & 0x7BE0);
+    }
+}
+
+impl Snapshot for LoopyRegister {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.v);
+        writer.write_u16(self.t);
+        writer.write_u8(self.x);
+        writer.write_bool(self.w);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.v = reader.read_u16()?;
+        self.t = reader.read_u16()?;
+        self.x = reader.read_u8()?;
+        self.w = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // the register walkthrough from the nesdev wiki
+    #[test]
+    fn test_register_writes() {
+        let mut loopy = LoopyRegister::new();
+        loopy.write_ctrl(0b0000_0000);
+        loopy.reset_latch();
+
+        loopy.write_scroll(0b0111_1101);
+        assert_eq!(loopy.t, 0b000_00_00000_01111);
+        assert_eq!(loopy.x, 0b101);
+        assert!(loopy.w);
+
+        loopy.write_scroll(0b0101_1110);
+        assert_eq!(loopy.t, 0b110_00_01011_01111);
+        assert!(!loopy.w);
+
+        loopy.write_addr(0b0011_1101);
+        assert_eq!(loopy.t, 0b011_11_01011_01111);
+
+        loopy.write_addr(0b1111_0000);
+        assert_eq!(loopy.t, 0b011_11_01111_10000);
+        assert_eq!(loopy.v, loopy.t);
+    }
+
+    #[test]
+    fn test_ctrl_sets_nametable_bits() {
+        let mut loopy = LoopyRegister::new();
+        loopy.write_ctrl(0b1000_0011);
+        assert_eq!(loopy.t, 0x0C00);
+        loopy.copy_horizontal();
+        assert_eq!(loopy.v, 0x0400);
+        loopy.copy_vertical();
+        assert_eq!(loopy.v, 0x0C00);
+    }
+
+    #[test]
+    fn test_coarse_x_wraps_into_next_nametable() {
+        let mut loopy = LoopyRegister::new();
+        loopy.v = 0x001F;
+        loopy.increment_coarse_x();
+        assert_eq!(loopy.v, 0x0400);
+        loopy.increment_coarse_x();
+        assert_eq!(loopy.v, 0x0401);
+    }
+
+    #[test]
+    fn test_y_increment() {
+        let mut loopy = LoopyRegister::new();
+        loopy.v = 0x6000 | (5 << 5);
+        loopy.increment_y();
+        assert_eq!(loopy.v, 0x7000 | (5 << 5));
+        loopy.increment_y();
+        assert_eq!(loopy.v, 6 << 5);
+
+        // row 29 is the last row of tiles
+        loopy.v = 0x7000 | (29 << 5);
+        loopy.increment_y();
+        assert_eq!(loopy.v, 0x0800);
+
+        // rows 30 and 31 wrap to 0 in the same nametable
+        loopy.v = 0x7000 | (31 << 5);
+        loopy.increment_y();
+        assert_eq!(loopy.v, 0);
+    }
+
+    #[test]
+    fn test_addr_increment_wraps_at_15_bits() {
+        let mut loopy = LoopyRegister::new();
+        loopy.write_addr(0x3F);
+        loopy.write_addr(0xFF);
+        loopy.increment(1);
+        assert_eq!(loopy.vram_addr(), 0);
+        loopy.increment(32);
+        assert_eq!(loopy.vram_addr(), 32);
+    }
+}
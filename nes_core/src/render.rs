@@ -0,0 +1,157 @@
+use crate::save_state::{Snapshot, StateError, StateReader, StateWriter};
+
+// The pattern shifters hold the tile being drawn in their high byte and the
+// next tile in their low byte. Attribute bits are widened to 8 bits when
+// loaded so that they shift in step with the pattern.
+pub struct BackgroundShifters {
+    pattern_lo: u16,
+    pattern_hi: u16,
+    attribute_lo: u16,
+    attribute_hi: u16,
+}
+
+impl Default for BackgroundShifters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BackgroundShifters {
+    pub fn new() -> Self {
+        BackgroundShifters {
+            pattern_lo: 0,
+            pattern_hi: 0,
+            attribute_lo: 0,
+            attribute_hi: 0,
+        }
+    }
+
+    pub fn load(&mut self, pattern_lo: u8, pattern_hi: u8, palette: u8) {
+        self.pattern_lo = (self.pattern_lo & 0xFF00) | pattern_lo as u16;
+        self.pattern_hi = (self.pattern_hi & 0xFF00) | pattern_hi as u16;
+        self.attribute_lo =
+            (self.attribute_lo & 0xFF00) | if palette & 0b01 != 0 { 0xFF } else { 0 };
+        self.attribute_hi =
+            (self.attribute_hi & 0xFF00) | if palette & 0b10 != 0 { 0xFF } else { 0 };
+    }
+
+    pub fn shift(&mut self) {
+        self.pattern_lo <<= 1;
+        self.pattern_hi <<= 1;
+        self.attribute_lo <<= 1;
+        self.attribute_hi <<= 1;
+    }
+
+    // (color index 0..=3, palette 0..=3) of the pixel selected by fine x
+    pub fn pixel(&self, fine_x: u8) -> (u8, u8) {
+        let mux = 0x8000 >> fine_x;
+        let bit = |shifter: u16| (shifter & mux != 0) as u8;
+        (
+            bit(self.pattern_hi) << 1 | bit(self.pattern_lo),
+            bit(self.attribute_hi) << 1 | bit(self.attribute_lo),
+        )
+    }
+}
+
+impl Snapshot for BackgroundShifters {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u16(self.pattern_lo);
+        writer.write_u16(self.pattern_hi);
+        writer.write_u16(self.attribute_lo);
+        writer.write_u16(self.attribute_hi);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.pattern_lo = reader.read_u16()?;
+        self.pattern_hi = reader.read_u16()?;
+        self.attribute_lo = reader.read_u16()?;
+        self.attribute_hi = reader.read_u16()?;
+        Ok(())
+    }
+}
+
+// A sprite fetched for the scanline being drawn. Pattern bytes are stored
+// already flipped horizontally, leftmost pixel in bit 7.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SpriteSlot {
+    pub x: u8,
+    pub attributes: u8,
+    pub pattern_lo: u8,
+    pub pattern_hi: u8,
+    pub sprite_zero: bool,
+}
+
+impl SpriteSlot {
+    // color index at screen column `x`, 0 when transparent or not covered
+    pub fn pixel(&self, x: usize) -> u8 {
+        let column = x.wrapping_sub(self.x as usize);
+        if column >= 8 {
+            return 0;
+        }
+        let bit = 7 - column;
+        ((self.pattern_hi >> bit) & 1) << 1 | ((self.pattern_lo >> bit) & 1)
+    }
+
+    pub fn palette(&self) -> u8 {
+        self.attributes & 0b11
+    }
+
+    pub fn behind_background(&self) -> bool {
+        self.attributes & 0b0010_0000 != 0
+    }
+}
+
+impl Snapshot for SpriteSlot {
+    fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.x);
+        writer.write_u8(self.attributes);
+        writer.write_u8(self.pattern_lo);
+        writer.write_u8(self.pattern_hi);
+        writer.write_bool(self.sprite_zero);
+    }
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError> {
+        self.x = reader.read_u8()?;
+        self.attributes = reader.read_u8()?;
+        self.pattern_lo = reader.read_u8()?;
+        self.pattern_hi = reader.read_u8()?;
+        self.sprite_zero = reader.read_bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_background_shifters_select_fine_x() {
+        let mut shifters = BackgroundShifters::new();
+        shifters.load(0b1000_0001, 0b0000_0001, 0b10);
+        for _ in 0..8 {
+            shifters.shift();
+        }
+
+        assert_eq!(shifters.pixel(0), (1, 2));
+        assert_eq!(shifters.pixel(1), (0, 2));
+        assert_eq!(shifters.pixel(7), (3, 2));
+    }
+
+    #[test]
+    fn test_sprite_slot_pixel() {
+        let sprite = SpriteSlot {
+            x: 250,
+            attributes: 0b0010_0011,
+            pattern_lo: 0b1000_0000,
+            pattern_hi: 0b1000_0001,
+            sprite_zero: false,
+        };
+
+        assert_eq!(sprite.pixel(249), 0);
+        assert_eq!(sprite.pixel(250), 3);
+        assert_eq!(sprite.pixel(257), 2);
+        assert_eq!(sprite.pixel(258), 0);
+        assert_eq!(sprite.palette(), 3);
+        assert!(sprite.behind_background());
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+
+// Ring buffer of machine snapshots for stepping the emulator backwards.
+//
+// Only the most recent snapshot is kept in full. Every older snapshot is
+// stored as the XOR of itself with its successor, run-length encoded, so that
+// the mostly unchanged bytes between consecutive frames cost almost nothing.
+pub struct RewindBuffer {
+    capacity: usize,
+    current: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        RewindBuffer {
+            capacity,
+            current: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, state: Vec<u8>) {
+        if let Some(previous) = self.current.take() {
+            if previous.len() == state.len() {
+                self.deltas.push_back(encode_delta(&previous, &state));
+                if self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                // a different machine layout: older snapshots cannot be rebuilt
+                self.deltas.clear();
+            }
+        }
+        self.current = Some(state);
+    }
+
+    // Drops the latest snapshot and returns the one recorded before it
+    pub fn rewind(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        let current = self.current.as_mut()?;
+        apply_delta(current, &delta);
+        Some(current.clone())
+    }
+
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+    }
+
+    pub fn memory_usage(&self) -> usize {
+        self.current.as_ref().map_or(0, |state| state.len())
+            + self.deltas.iter().map(|delta| delta.len()).sum::<usize>()
+    }
+}
+
+// Encodes `older ^ newer` as a sequence of (zero run, literal run, literals)
+fn encode_delta(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    let mut i = 0;
+    while i < newer.len() {
+        let zeros_start = i;
+        while i < newer.len() && older[i] == newer[i] {
+            i += 1;
+        }
+        let literals_start = i;
+        while i < newer.len() && older[i] != newer[i] {
+            i += 1;
+        }
+
+        write_varint(&mut encoded, literals_start - zeros_start);
+        write_varint(&mut encoded, i - literals_start);
+        encoded.extend((literals_start..i).map(|j| older[j] ^ newer[j]));
+    }
+    encoded
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut i = 0;
+    while i < delta.len() {
+        pos += read_varint(delta, &mut i);
+        let literals = read_varint(delta, &mut i);
+        for byte in &delta[i..i + literals] {
+            state[pos] ^= byte;
+            pos += 1;
+        }
+        i += literals;
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        nes::Nes,
+        rom::{test, Mirroring, Rom},
+    };
+
+    fn state(seed: u8) -> Vec<u8> {
+        let mut state = vec![0; 4096];
+        state[10] = seed;
+        state[2000] = seed.wrapping_mul(3);
+        state[4095] = !seed;
+        state
+    }
+
+    #[test]
+    fn test_rewind_steps_back_one_snapshot_at_a_time() {
+        let mut rewind = RewindBuffer::new(10);
+        for seed in 0..5 {
+            rewind.push(state(seed));
+        }
+
+        assert_eq!(rewind.rewind(), Some(state(3)));
+        assert_eq!(rewind.rewind(), Some(state(2)));
+        assert_eq!(rewind.rewind(), Some(state(1)));
+        assert_eq!(rewind.rewind(), Some(state(0)));
+        assert_eq!(rewind.rewind(), None);
+    }
+
+    #[test]
+    fn test_recording_resumes_after_rewind() {
+        let mut rewind = RewindBuffer::new(10);
+        rewind.push(state(1));
+        rewind.push(state(2));
+        rewind.push(state(3));
+        assert_eq!(rewind.rewind(), Some(state(2)));
+
+        rewind.push(state(7));
+        assert_eq!(rewind.rewind(), Some(state(2)));
+        assert_eq!(rewind.rewind(), Some(state(1)));
+    }
+
+    #[test]
+    fn test_capacity_drops_oldest_snapshots() {
+        let mut rewind = RewindBuffer::new(3);
+        for seed in 0..10 {
+            rewind.push(state(seed));
+        }
+
+        assert_eq!(rewind.len(), 3);
+        assert_eq!(rewind.rewind(), Some(state(8)));
+        assert_eq!(rewind.rewind(), Some(state(7)));
+        assert_eq!(rewind.rewind(), Some(state(6)));
+        assert_eq!(rewind.rewind(), None);
+    }
+
+    #[test]
+    fn test_deltas_are_compressed() {
+        let mut rewind = RewindBuffer::new(100);
+        for seed in 0..100 {
+            rewind.push(state(seed));
+        }
+
+        assert!(rewind.memory_usage() < 2 * 4096);
+    }
+
+    #[test]
+    fn test_delta_round_trip() {
+        let older: Vec<u8> = (0..=255).collect();
+        let mut newer = older.clone();
+        newer[0] = 0xFF;
+        newer[128..200].iter_mut().for_each(|byte| *byte = 0);
+
+        let mut restored = newer.clone();
+        apply_delta(&mut restored, &encode_delta(&older, &newer));
+        assert_eq!(restored, older);
+    }
+
+    // Spins on INC $00 with the vblank NMI bumping $01, so every frame differs
+    fn spinning_rom() -> Rom {
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+            0xE6, 0x00, 0x4C, 0x05, 0x80, // loop: INC $00, JMP loop
+        ];
+        let nmi_handler = [
+            0xE6, 0x01, // INC $01
+            0x40, // RTI
+        ];
+        prg_rom[0..program.len()].copy_from_slice(&program);
+        prg_rom[0x20..0x20 + nmi_handler.len()].copy_from_slice(&nmi_handler);
+        prg_rom[0x7FFA..].copy_from_slice(&[0x20, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        test::test_rom_with(prg_rom, vec![], 0, Mirroring::HORIZONTAL)
+    }
+
+    #[test]
+    fn test_rewinding_a_running_machine() {
+        let mut nes = Nes::new(spinning_rom());
+        let mut rewind = RewindBuffer::new(10);
+        let mut states = Vec::new();
+        for _ in 0..6 {
+            nes.run_frame();
+            let state = nes.save_state();
+            rewind.push(state.clone());
+            states.push(state);
+        }
+        assert!(states[0] != states[1]);
+
+        for expected in states[..5].iter().rev() {
+            nes.load_state(&rewind.rewind().unwrap()).unwrap();
+            assert_eq!(&nes.save_state(), expected);
+        }
+        assert!(rewind.rewind().is_none());
+
+        // the restored machine picks up exactly where it left off
+        nes.run_frame();
+        assert_eq!(nes.save_state(), states[1]);
+    }
+
+    #[test]
+    fn test_holding_rewind_steps_back_one_frame_at_a_time() {
+        // the frontend loop: snapshot at the start of each frame, or load the
+        // previous snapshot while rewinding, then run a frame
+        let mut nes = Nes::new(spinning_rom());
+        let mut rewind = RewindBuffer::new(10);
+        let mut shown = Vec::new();
+        for rewinding in [false, false, false, false, true, true, true] {
+            if !rewinding {
+                rewind.push(nes.save_state());
+            } else if let Some(state) = rewind.rewind() {
+                nes.load_state(&state).unwrap();
+            }
+            nes.run_frame();
+            shown.push(nes.frame_count());
+        }
+
+        assert_eq!(shown, [1, 2, 3, 4, 3, 2, 1]);
+    }
+}
@@ -0,0 +1,743 @@
+use std::fmt;
+
+use crate::mapper;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Mirroring {
+    VERTICAL,
+    HORIZONTAL,
+    FOUR_SCREEN,
+    SINGLE_SCREEN_LOWER,
+    SINGLE_SCREEN_UPPER,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum Timing {
+    NTSC,
+    PAL,
+    MULTI_REGION,
+    DENDY,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+#[allow(non_camel_case_types)]
+pub enum ConsoleType {
+    NES,
+    VS_SYSTEM,
+    PLAYCHOICE_10,
+    EXTENDED(u8),
+}
+
+pub struct Rom {
+    pub prg_rom: Vec<u8>,
+    pub chr_rom: Vec<u8>,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub screen_mirroring: Mirroring,
+    pub battery: bool,
+    pub is_nes2: bool,
+
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub misc_roms: u8,
+    pub expansion_device: u8,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    BadMagic,
+    TruncatedHeader {
+        len: usize,
+    },
+    TrainerMissing {
+        offset: usize,
+        len: usize,
+    },
+    TruncatedPrgRom {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    TruncatedChrRom {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+    RomTooLarge {
+        header_offset: usize,
+    },
+    UnalignedRomSize {
+        header_offset: usize,
+        size: usize,
+    },
+    EmptyPrgRom,
+    UnsupportedMapper(u16),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::BadMagic => write!(f, "File is not in iNES file format"),
+            RomError::TruncatedHeader { len } => {
+                write!(f, "Header is truncated: expected 16 bytes, got {}", len)
+            }
+            RomError::TrainerMissing { offset, len } => write!(
+                f,
+                "Trainer at offset {:#x} is missing: expected 512 bytes, got {}",
+                offset, len
+            ),
+            RomError::TruncatedPrgRom {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "PRG-ROM at offset {:#x} is truncated: expected {} bytes, got {}",
+                offset, expected, actual
+            ),
+            RomError::TruncatedChrRom {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CHR-ROM at offset {:#x} is truncated: expected {} bytes, got {}",
+                offset, expected, actual
+            ),
+            RomError::RomTooLarge { header_offset } => write!(
+                f,
+                "ROM size declared at header offset {} is too large",
+                header_offset
+            ),
+            RomError::UnalignedRomSize {
+                header_offset,
+                size,
+            } => write!(
+                f,
+                "ROM size {} declared at header offset {} is not a whole number of pages",
+                size, header_offset
+            ),
+            RomError::EmptyPrgRom => write!(f, "ROM has no PRG-ROM"),
+            RomError::UnsupportedMapper(mapper) => write!(f, "Mapper {} is not supported", mapper),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+const NES_TAG: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const PRG_ROM_PAGE_SIZE: usize = 16384;
+const CHR_ROM_PAGE_SIZE: usize = 8192;
+const PRG_RAM_PAGE_SIZE: usize = 8192;
+// the smallest PRG bank any board switches
+const PRG_ROM_BANK_SIZE: usize = 8192;
+
+impl Rom {
+    pub fn new(raw: &[u8]) -> Result<Rom, RomError> {
+        if raw.len() < NES_TAG.len() || raw[0..4] != NES_TAG {
+            return Err(RomError::BadMagic);
+        }
+        if raw.len() < HEADER_SIZE {
+            return Err(RomError::TruncatedHeader { len: raw.len() });
+        }
+
+        let ines_ver = (raw[7] >> 2) & 0b11;
+        let is_nes2 = ines_ver == 2;
+
+        let mapper = match ines_ver {
+            2 => {
+                ((raw[8] as u16 & 0b1111) << 8)
+                    | (raw[7] & 0b1111_0000) as u16
+                    | (raw[6] >> 4) as u16
+            }
+            0 => ((raw[7] & 0b1111_0000) | (raw[6] >> 4)) as u16,
+            // archaic iNES: bytes 7-15 may hold garbage such as "DiskDude!"
+            _ => (raw[6] >> 4) as u16,
+        };
+        let submapper = if is_nes2 { raw[8] >> 4 } else { 0 };
+
+        if !mapper::is_supported(mapper) {
+            return Err(RomError::UnsupportedMapper(mapper));
+        }
+
+        let four_screen = raw[6] & 0b1000 != 0;
+        let vertical_mirroring = raw[6] & 0b1 != 0;
+        let screen_mirroring = match (four_screen, vertical_mirroring) {
+            (true, _) => Mirroring::FOUR_SCREEN,
+            (false, true) => Mirroring::VERTICAL,
+            (false, false) => Mirroring::HORIZONTAL,
+        };
+
+        let (prg_rom_size, chr_rom_size) = if is_nes2 {
+            (
+                nes2_rom_size(raw[4], raw[9] & 0b1111, PRG_ROM_PAGE_SIZE)
+                    .ok_or(RomError::RomTooLarge { header_offset: 4 })?,
+                nes2_rom_size(raw[5], raw[9] >> 4, CHR_ROM_PAGE_SIZE)
+                    .ok_or(RomError::RomTooLarge { header_offset: 5 })?,
+            )
+        } else {
+            (
+                raw[4] as usize * PRG_ROM_PAGE_SIZE,
+                raw[5] as usize * CHR_ROM_PAGE_SIZE,
+            )
+        };
+
+        if prg_rom_size == 0 {
+            return Err(RomError::EmptyPrgRom);
+        }
+        // exponent-multiplier sizes can be a few bytes, which no board maps
+        if prg_rom_size % PRG_ROM_BANK_SIZE != 0 {
+            return Err(RomError::UnalignedRomSize {
+                header_offset: 4,
+                size: prg_rom_size,
+            });
+        }
+        if chr_rom_size % CHR_ROM_PAGE_SIZE != 0 {
+            return Err(RomError::UnalignedRomSize {
+                header_offset: 5,
+                size: chr_rom_size,
+            });
+        }
+
+        let console_type = match raw[7] & 0b11 {
+            0 => ConsoleType::NES,
+            1 => ConsoleType::VS_SYSTEM,
+            2 => ConsoleType::PLAYCHOICE_10,
+            _ if is_nes2 => ConsoleType::EXTENDED(raw[13] & 0b1111),
+            _ => ConsoleType::NES,
+        };
+
+        let (prg_ram_size, prg_nvram_size, mut chr_ram_size, chr_nvram_size) = if is_nes2 {
+            (
+                nes2_ram_size(raw[10] & 0b1111),
+                nes2_ram_size(raw[10] >> 4),
+                nes2_ram_size(raw[11] & 0b1111),
+                nes2_ram_size(raw[11] >> 4),
+            )
+        } else {
+            // iNES only gives the PRG-RAM size, where 0 infers 8KB for compatibility
+            (
+                (raw[8] as usize).max(1) * PRG_RAM_PAGE_SIZE,
+                0,
+                if chr_rom_size == 0 {
+                    CHR_ROM_PAGE_SIZE
+                } else {
+                    0
+                },
+                0,
+            )
+        };
+        // a board without CHR-ROM needs some CHR-RAM, even if the header forgot it
+        if chr_rom_size == 0 && chr_ram_size + chr_nvram_size == 0 {
+            chr_ram_size = CHR_ROM_PAGE_SIZE;
+        }
+
+        let timing = match (is_nes2, raw[12] & 0b11, raw[9] & 0b1) {
+            (true, 0, _) => Timing::NTSC,
+            (true, 1, _) => Timing::PAL,
+            (true, 2, _) => Timing::MULTI_REGION,
+            (true, _, _) => Timing::DENDY,
+            (false, _, 0) => Timing::NTSC,
+            (false, _, _) => Timing::PAL,
+        };
+
+        let battery = raw[6] & 0b10 != 0;
+        let skip_trainer = raw[6] & 0b100 != 0;
+
+        if skip_trainer && raw.len() < HEADER_SIZE + TRAINER_SIZE {
+            return Err(RomError::TrainerMissing {
+                offset: HEADER_SIZE,
+                len: raw.len() - HEADER_SIZE,
+            });
+        }
+
+        let prg_rom_start = HEADER_SIZE + if skip_trainer { TRAINER_SIZE } else { 0 };
+        let prg_rom =
+            section(raw, prg_rom_start, prg_rom_size).ok_or_else(|| RomError::TruncatedPrgRom {
+                offset: prg_rom_start,
+                expected: prg_rom_size,
+                actual: raw.len() - prg_rom_start,
+            })?;
+
+        let chr_rom_start = prg_rom_start + prg_rom_size;
+        let chr_rom =
+            section(raw, chr_rom_start, chr_rom_size).ok_or_else(|| RomError::TruncatedChrRom {
+                offset: chr_rom_start,
+                expected: chr_rom_size,
+                actual: raw.len() - chr_rom_start,
+            })?;
+
+        Ok(Rom {
+            prg_rom: prg_rom.to_vec(),
+            chr_rom: chr_rom.to_vec(),
+            mapper,
+            submapper,
+            screen_mirroring,
+            battery,
+            is_nes2,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size,
+            timing,
+            console_type,
+            misc_roms: if is_nes2 { raw[14] & 0b11 } else { 0 },
+            expansion_device: if is_nes2 { raw[15] & 0b11_1111 } else { 0 },
+        })
+    }
+}
+
+// NES 2.0 ROM sizes are either a 12-bit count of pages, or, when the MSB
+// nibble is $F, an exponent-multiplier pair: 2^E * (MM * 2 + 1) bytes
+fn nes2_rom_size(lsb: u8, msb: u8, page_size: usize) -> Option<usize> {
+    if msb == 0b1111 {
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = (lsb & 0b11) as usize * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+    } else {
+        Some((((msb as usize) << 8) | lsb as usize) * page_size)
+    }
+}
+
+fn section(raw: &[u8], start: usize, len: usize) -> Option<&[u8]> {
+    raw.get(start..start.checked_add(len)?)
+}
+
+// RAM sizes are stored as shift counts: 64 << n bytes, 0 meaning none
+fn nes2_ram_size(shift: u8) -> usize {
+    if shift == 0 {
+        0
+    } else {
+        64 << shift
+    }
+}
+
+pub mod test {
+
+    use super::*;
+
+    struct TestRom {
+        header: Vec<u8>,
+        trainer: Option<Vec<u8>>,
+        pgp_rom: Vec<u8>,
+        chr_rom: Vec<u8>,
+    }
+
+    fn create_rom(rom: TestRom) -> Vec<u8> {
+        let mut result = Vec::with_capacity(
+            rom.header.len()
+                + rom.trainer.as_ref().map_or(0, |t| t.len())
+                + rom.pgp_rom.len()
+                + rom.chr_rom.len(),
+        );
+
+        result.extend(&rom.header);
+        if let Some(t) = rom.trainer {
+            result.extend(t);
+        }
+        result.extend(&rom.pgp_rom);
+        result.extend(&rom.chr_rom);
+
+        result
+    }
+
+    pub fn test_rom_with(
+        prg_rom: Vec<u8>,
+        chr_rom: Vec<u8>,
+        mapper: u16,
+        screen_mirroring: Mirroring,
+    ) -> Rom {
+        Rom {
+            chr_ram_size: if chr_rom.is_empty() {
+                CHR_ROM_PAGE_SIZE
+            } else {
+                0
+            },
+            prg_rom,
+            chr_rom,
+            mapper,
+            submapper: 0,
+            screen_mirroring,
+            battery: false,
+            is_nes2: false,
+            prg_ram_size: PRG_RAM_PAGE_SIZE,
+            prg_nvram_size: 0,
+            chr_nvram_size: 0,
+            timing: Timing::NTSC,
+            console_type: ConsoleType::NES,
+            misc_roms: 0,
+            expansion_device: 0,
+        }
+    }
+
+    pub fn test_rom() -> Rom {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+
+        Rom::new(&test_rom).unwrap()
+    }
+
+    #[test]
+    fn test() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x31, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.chr_rom, vec!(2; 1 * CHR_ROM_PAGE_SIZE));
+        assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
+        assert_eq!(rom.mapper, 3);
+        assert_eq!(rom.screen_mirroring, Mirroring::VERTICAL);
+        assert!(!rom.battery);
+    }
+
+    #[test]
+    fn test_battery_flag() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x12, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 1 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert!(rom.battery);
+        assert_eq!(rom.mapper, 1);
+        assert_eq!(rom.prg_ram_size, 8192);
+    }
+
+    #[test]
+    fn test_with_trainer() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E,
+                0x45,
+                0x53,
+                0x1A,
+                0x02,
+                0x01,
+                0x31 | 0b100,
+                00,
+                00,
+                00,
+                00,
+                00,
+                00,
+                00,
+                00,
+                00,
+            ],
+            trainer: Some(vec![0; 512]),
+            pgp_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.chr_rom, vec!(2; 1 * CHR_ROM_PAGE_SIZE));
+        assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
+        assert_eq!(rom.mapper, 3);
+        assert_eq!(rom.screen_mirroring, Mirroring::VERTICAL);
+    }
+
+    #[test]
+    fn test_nes2_header() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x02, 0x01, 0x41, 0x08, 0x10, 00, 0x07, 0x90, 0x01, 00,
+                0x01, 0x23,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 2 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert!(rom.is_nes2);
+        assert_eq!(rom.mapper, 4);
+        assert_eq!(rom.submapper, 1);
+        assert_eq!(rom.prg_rom, vec!(1; 2 * PRG_ROM_PAGE_SIZE));
+        assert_eq!(rom.chr_rom, vec!(2; 1 * CHR_ROM_PAGE_SIZE));
+        assert_eq!(rom.prg_ram_size, 8192);
+        assert_eq!(rom.prg_nvram_size, 0);
+        assert_eq!(rom.chr_ram_size, 0);
+        assert_eq!(rom.chr_nvram_size, 32768);
+        assert_eq!(rom.timing, Timing::PAL);
+        assert_eq!(rom.console_type, ConsoleType::NES);
+        assert_eq!(rom.misc_roms, 1);
+        assert_eq!(rom.expansion_device, 0x23);
+    }
+
+    #[test]
+    fn test_nes2_exponent_multiplier_size() {
+        // 2^15 * (1 * 2 + 1) = 96KB of PRG-ROM
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E,
+                0x45,
+                0x53,
+                0x1A,
+                0b0011_1101,
+                0x00,
+                0x11,
+                0x08,
+                00,
+                0x0F,
+                00,
+                0x07,
+                0x03,
+                00,
+                00,
+                00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 3 * 0x8000],
+            chr_rom: vec![],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.mapper, 1);
+        assert_eq!(rom.prg_rom.len(), 3 * 0x8000);
+        assert_eq!(rom.chr_rom.len(), 0);
+        assert_eq!(rom.chr_ram_size, 8192);
+        assert_eq!(rom.timing, Timing::DENDY);
+    }
+
+    #[test]
+    fn test_nes2_without_chr_gets_chr_ram() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x00, 0x00, 0x08, 00, 00, 00, 00, 00, 00, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 1 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![],
+        });
+
+        let rom: Rom = Rom::new(&test_rom).unwrap();
+
+        assert_eq!(rom.chr_ram_size, 8192);
+        assert_eq!(rom.chr_nvram_size, 0);
+    }
+
+    #[test]
+    fn test_nes2_extended_mapper_number() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x00, 0x0B, 0x01, 00, 00, 00, 00, 0x05, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 1 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+        let rom = Rom::new(&test_rom);
+        match rom {
+            Result::Ok(_) => assert!(false, "should not load rom"),
+            Result::Err(err) => assert_eq!(err, RomError::UnsupportedMapper(256)),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_mapper() {
+        let test_rom = create_rom(TestRom {
+            header: vec![
+                0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01, 0x51, 00, 00, 00, 00, 00, 00, 00, 00, 00,
+            ],
+            trainer: None,
+            pgp_rom: vec![1; 1 * PRG_ROM_PAGE_SIZE],
+            chr_rom: vec![2; 1 * CHR_ROM_PAGE_SIZE],
+        });
+        let rom = Rom::new(&test_rom);
+        match rom {
+            Result::Ok(_) => assert!(false, "should not load rom"),
+            Result::Err(err) => assert_eq!(err, RomError::UnsupportedMapper(5)),
+        }
+    }
+
+    #[cfg(test)]
+    fn nrom_header(prg_pages: u8, chr_pages: u8, flags_6: u8) -> Vec<u8> {
+        vec![
+            0x4E, 0x45, 0x53, 0x1A, prg_pages, chr_pages, flags_6, 00, 00, 00, 00, 00, 00, 00, 00,
+            00,
+        ]
+    }
+
+    #[test]
+    fn test_malformed_headers() {
+        let mut truncated_prg = nrom_header(2, 1, 0);
+        truncated_prg.extend(vec![1; PRG_ROM_PAGE_SIZE]);
+
+        let mut truncated_chr = nrom_header(1, 1, 0);
+        truncated_chr.extend(vec![1; PRG_ROM_PAGE_SIZE + 10]);
+
+        let mut missing_trainer = nrom_header(1, 1, 0b100);
+        missing_trainer.extend(vec![0; 100]);
+
+        let mut huge_nes2_prg = nrom_header(0xFF, 0, 0);
+        huge_nes2_prg[7] = 0x08;
+        huge_nes2_prg[9] = 0x0F;
+
+        let mut huge_nes2_chr = nrom_header(1, 0xFE, 0);
+        huge_nes2_chr[7] = 0x08;
+        huge_nes2_chr[9] = 0xF0;
+        huge_nes2_chr.extend(vec![1; PRG_ROM_PAGE_SIZE]);
+
+        let mut truncated_nes2_chr = huge_nes2_chr.clone();
+        truncated_nes2_chr[5] = 0xFC;
+
+        // 2^2 * 3 = 12 bytes of PRG-ROM
+        let mut tiny_prg = nrom_header(0b0000_1001, 1, 0);
+        tiny_prg[7] = 0x08;
+        tiny_prg[9] = 0x0F;
+        tiny_prg.extend(vec![1; 12 + CHR_ROM_PAGE_SIZE]);
+
+        // 2^0 * 1 = 1 byte of CHR-ROM
+        let mut one_byte_chr = nrom_header(1, 0, 0);
+        one_byte_chr[7] = 0x08;
+        one_byte_chr[9] = 0xF0;
+        one_byte_chr.extend(vec![1; PRG_ROM_PAGE_SIZE + 1]);
+
+        let mut empty_prg = nrom_header(0, 1, 0);
+        empty_prg.extend(vec![2; CHR_ROM_PAGE_SIZE]);
+
+        let corpus: Vec<(Vec<u8>, RomError)> = vec![
+            (vec![], RomError::BadMagic),
+            (vec![0x4E, 0x45, 0x53], RomError::BadMagic),
+            (vec![0x4E, 0x45, 0x53, 0x00, 0, 0, 0, 0], RomError::BadMagic),
+            (
+                vec![0x4E, 0x45, 0x53, 0x1A, 0x01, 0x01],
+                RomError::TruncatedHeader { len: 6 },
+            ),
+            (
+                truncated_prg,
+                RomError::TruncatedPrgRom {
+                    offset: 16,
+                    expected: 2 * PRG_ROM_PAGE_SIZE,
+                    actual: PRG_ROM_PAGE_SIZE,
+                },
+            ),
+            (
+                truncated_chr,
+                RomError::TruncatedChrRom {
+                    offset: 16 + PRG_ROM_PAGE_SIZE,
+                    expected: CHR_ROM_PAGE_SIZE,
+                    actual: 10,
+                },
+            ),
+            (
+                missing_trainer,
+                RomError::TrainerMissing {
+                    offset: 16,
+                    len: 100,
+                },
+            ),
+            (huge_nes2_prg, RomError::RomTooLarge { header_offset: 4 }),
+            (huge_nes2_chr, RomError::RomTooLarge { header_offset: 5 }),
+            (
+                truncated_nes2_chr,
+                RomError::TruncatedChrRom {
+                    offset: 16 + PRG_ROM_PAGE_SIZE,
+                    expected: 1 << 63,
+                    actual: 0,
+                },
+            ),
+            (empty_prg, RomError::EmptyPrgRom),
+            (
+                tiny_prg,
+                RomError::UnalignedRomSize {
+                    header_offset: 4,
+                    size: 12,
+                },
+            ),
+            (
+                one_byte_chr,
+                RomError::UnalignedRomSize {
+                    header_offset: 5,
+                    size: 1,
+                },
+            ),
+        ];
+
+        for (raw, expected) in corpus {
+            match Rom::new(&raw) {
+                Result::Ok(_) => assert!(false, "should not load rom, expected {:?}", expected),
+                Result::Err(err) => assert_eq!(err, expected),
+            }
+        }
+    }
+
+    #[test]
+    fn test_loader_never_panics_on_random_input() {
+        use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+        const SUPPORTED_MAPPERS: [u8; 6] = [0, 1, 2, 3, 4, 7];
+
+        let mut rng = StdRng::seed_from_u64(0x4E45531A);
+        for _ in 0..1000 {
+            let len = rng.gen_range(0..(16 + 512 + 2 * PRG_ROM_PAGE_SIZE));
+            let mut raw = vec![0u8; len];
+            rng.fill(&mut raw[..]);
+            if len >= 4 && rng.gen_bool(0.9) {
+                raw[0..4].copy_from_slice(&NES_TAG);
+            }
+            if len >= 6 && rng.gen_bool(0.5) {
+                // keep the declared sizes close to the actual length
+                raw[4] = rng.gen_range(0..3);
+                raw[5] = rng.gen_range(0..2);
+                if rng.gen_bool(0.5) {
+                    let declared =
+                        raw[4] as usize * PRG_ROM_PAGE_SIZE + raw[5] as usize * CHR_ROM_PAGE_SIZE;
+                    raw.resize(HEADER_SIZE + declared, 0);
+                }
+            }
+            if raw.len() >= 9 {
+                // pick a mapper the loader accepts so the mapper gets built
+                let mapper = *SUPPORTED_MAPPERS.choose(&mut rng).unwrap();
+                raw[6] = (raw[6] & 0b1111) | (mapper << 4);
+                raw[7] &= 0b1111;
+                raw[8] &= 0b1111_0000;
+            }
+
+            if let Ok(rom) = Rom::new(&raw) {
+                let mapper = mapper::new_mapper(rom);
+                let mut mapper = mapper.borrow_mut();
+                for _ in 0..16 {
+                    let addr = rng.gen_range(0x8000..=0xFFFF);
+                    mapper.write_prg(addr, rng.gen());
+                    mapper.read_prg(rng.gen_range(0x8000..=0xFFFF));
+                    mapper.read_prg_ram(rng.gen_range(0x6000..=0x7FFF));
+                    mapper.read_chr(rng.gen_range(0x0000..=0x1FFF));
+                    mapper.write_chr(rng.gen_range(0x0000..=0x1FFF), rng.gen());
+                }
+            }
+        }
+    }
+}
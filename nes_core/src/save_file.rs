@@ -0,0 +1,128 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::mapper::Mapper;
+
+// Battery-backed PRG-RAM lives in a .sav file next to the ROM
+pub fn save_path(rom_path: &Path) -> PathBuf {
+    rom_path.with_extension("sav")
+}
+
+// Returns the RAM contents as loaded, empty when there was nothing to load
+pub fn load(path: &Path, mapper: &mut dyn Mapper) -> io::Result<Vec<u8>> {
+    if mapper.save_data().is_none() {
+        return Ok(Vec::new());
+    }
+
+    match fs::read(path) {
+        Ok(data) => {
+            mapper.load_save_data(&data);
+            Ok(mapper.save_data().unwrap_or_default().to_vec())
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err),
+    }
+}
+
+pub struct SaveFile {
+    path: PathBuf,
+    last_flushed: Vec<u8>,
+}
+
+impl SaveFile {
+    // `loaded` is what `load` read from the file, so an untouched save is
+    // not rewritten
+    pub fn new(path: PathBuf, loaded: Vec<u8>) -> Self {
+        SaveFile {
+            path,
+            last_flushed: loaded,
+        }
+    }
+
+    // only touches the disk when the RAM changed since the last flush
+    pub fn flush(&mut self, mapper: &dyn Mapper) -> io::Result<()> {
+        let data = match mapper.save_data() {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+        if data == self.last_flushed.as_slice() {
+            return Ok(());
+        }
+
+        let tmp_path = self.path.with_extension("sav.tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.last_flushed = data.to_vec();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        mappers::nrom::Nrom,
+        rom::{test, Mirroring},
+    };
+
+    fn battery_nrom() -> Nrom {
+        let mut rom = test::test_rom_with(vec![0; 0x4000], vec![0; 0x2000], 0, Mirroring::VERTICAL);
+        rom.battery = true;
+        Nrom::new(rom)
+    }
+
+    #[test]
+    fn test_save_path_replaces_extension() {
+        assert_eq!(
+            save_path(Path::new("roms/zelda.nes")),
+            PathBuf::from("roms/zelda.sav")
+        );
+    }
+
+    #[test]
+    fn test_flush_and_load_round_trip() {
+        let path = std::env::temp_dir().join(format!("nes_emulator_{}.sav", std::process::id()));
+
+        let mut nrom = battery_nrom();
+        nrom.write_prg_ram(0x6000, 0x42);
+        let mut save_file = SaveFile::new(path.clone(), Vec::new());
+        save_file.flush(&nrom).unwrap();
+
+        let mut restored = battery_nrom();
+        load(&path, &mut restored).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(restored.read_prg_ram(0x6000), 0x42);
+    }
+
+    #[test]
+    fn test_unchanged_save_is_not_rewritten() {
+        let path =
+            std::env::temp_dir().join(format!("nes_emulator_unchanged_{}.sav", std::process::id()));
+        let mut nrom = battery_nrom();
+        nrom.write_prg_ram(0x6000, 0x42);
+        SaveFile::new(path.clone(), Vec::new())
+            .flush(&nrom)
+            .unwrap();
+
+        let mut restored = battery_nrom();
+        let loaded = load(&path, &mut restored).unwrap();
+        // a stale file would show up if the flush wrote anything
+        fs::write(&path, b"stale").unwrap();
+        SaveFile::new(path.clone(), loaded)
+            .flush(&restored)
+            .unwrap();
+        let on_disk = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(on_disk, b"stale");
+    }
+
+    #[test]
+    fn test_missing_save_file_is_not_an_error() {
+        let mut nrom = battery_nrom();
+        assert!(load(Path::new("does/not/exist.sav"), &mut nrom).is_ok());
+    }
+}
@@ -0,0 +1,336 @@
+use std::fmt;
+
+// Save states are a flat little-endian byte stream: a header followed by each
+// component writing its fields in a fixed order
+const STATE_MAGIC: [u8; 4] = [0x4E, 0x45, 0x53, 0x53]; // "NESS"
+pub const STATE_VERSION: u8 = 13;
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated {
+        offset: usize,
+    },
+    SizeMismatch {
+        offset: usize,
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "Data is not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "Save state version {} is not supported", version)
+            }
+            StateError::Truncated { offset } => {
+                write!(f, "Save state is truncated at offset {:#x}", offset)
+            }
+            StateError::SizeMismatch {
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Block at offset {:#x} has {} bytes, expected {}",
+                offset, actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+pub trait Snapshot {
+    fn save_state(&self, writer: &mut StateWriter);
+
+    fn load_state(&mut self, reader: &mut StateReader) -> Result<(), StateError>;
+}
+
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl Default for StateWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        let mut data = Vec::new();
+        data.extend(STATE_MAGIC);
+        data.push(STATE_VERSION);
+        StateWriter { data }
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.data.push(value as u8);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.data.extend(value.to_le_bytes());
+    }
+
+    // length-prefixed so that a state from a different cartridge is rejected
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.data.extend(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, StateError> {
+        if data.len() < STATE_MAGIC.len() || data[0..4] != STATE_MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let mut reader = StateReader { data, pos: 4 };
+        let version = reader.read_u8()?;
+        if version != STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(StateError::Truncated { offset: self.pos })?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, StateError> {
+        Ok(self.read_u8()? != 0)
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, StateError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64, StateError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<(), StateError> {
+        let offset = self.pos;
+        let len = self.read_u32()? as usize;
+        if len != bytes.len() {
+            return Err(StateError::SizeMismatch {
+                offset,
+                expected: bytes.len(),
+                actual: len,
+            });
+        }
+        bytes.copy_from_slice(self.take(len)?);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        nes::Nes,
+        rom::{test, Mirroring, Rom},
+    };
+
+    // Enables NMI and rendering, then spins until the NMI handler has run six
+    // times. Each NMI changes the backdrop color and scroll so frames differ.
+    fn frame_counter_rom() -> Rom {
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0x78, // SEI
+            0xA9, 0x80, 0x8D, 0x00, 0x20, // LDA #$80, STA $2000
+            0xA9, 0x1E, 0x8D, 0x01, 0x20, // LDA #$1E, STA $2001
+            0xA5, 0x10, 0xC9, 0x06, 0xD0, 0xFA, // loop: LDA $10, CMP #$06, BNE loop
+            0x00, // BRK
+        ];
+        let nmi_handler = [
+            0xE6, 0x10, // INC $10
+            0xA9, 0x3F, 0x8D, 0x06, 0x20, // LDA #$3F, STA $2006
+            0xA9, 0x00, 0x8D, 0x06, 0x20, // LDA #$00, STA $2006
+            0xA5, 0x10, 0x8D, 0x07, 0x20, // LDA $10, STA $2007
+            0x8D, 0x05, 0x20, 0x8D, 0x05, 0x20, // STA $2005, STA $2005
+            0x40, // RTI
+        ];
+        prg_rom[0..program.len()].copy_from_slice(&program);
+        prg_rom[0x20..0x20 + nmi_handler.len()].copy_from_slice(&nmi_handler);
+        prg_rom[0x7FFA..].copy_from_slice(&[0x20, 0x80, 0x00, 0x80, 0x00, 0x80]);
+
+        let chr_rom = (0..0x2000).map(|i| (i * 7) as u8).collect();
+        test::test_rom_with(prg_rom, chr_rom, 0, Mirroring::HORIZONTAL)
+    }
+
+    #[test]
+    fn test_restored_machine_renders_identical_frames() {
+        let mut nes_a = Nes::new(frame_counter_rom());
+        let mut frames_a = Vec::new();
+        let mut snapshot = None;
+        let mut frames_at_snapshot = 0;
+        loop {
+            if snapshot.is_none() && nes_a.ram()[0x10] == 2 {
+                snapshot = Some(nes_a.save_state());
+                frames_at_snapshot = frames_a.len();
+            }
+            match nes_a.run_frame() {
+                Some(frame) => frames_a.push(frame.data.clone()),
+                None => break,
+            }
+        }
+
+        let mut nes_b = Nes::new(frame_counter_rom());
+        nes_b.load_state(&snapshot.unwrap()).unwrap();
+        let mut frames_b = Vec::new();
+        while let Some(frame) = nes_b.run_frame() {
+            frames_b.push(frame.data.clone());
+        }
+
+        assert_eq!(frames_a.len(), 6);
+        assert_eq!(frames_b.len(), 6 - frames_at_snapshot);
+        assert!(frames_b[0] != frames_b[1]);
+        assert!(frames_a[frames_at_snapshot..] == frames_b[..]);
+        assert_eq!(nes_a.save_state(), nes_b.save_state());
+    }
+
+    // Loops a DMC sample while spinning on zero page writes, so the DMC
+    // fetches keep landing on write cycles
+    fn dmc_rom() -> Rom {
+        let mut prg_rom = vec![0xEA; 0x8000];
+        let program = [
+            0xA9, 0x4F, 0x8D, 0x10, 0x40, // LDA #$4F, STA $4010
+            0xA9, 0x00, 0x8D, 0x12, 0x40, // LDA #$00, STA $4012
+            0xA9, 0xFF, 0x8D, 0x13, 0x40, // LDA #$FF, STA $4013
+            0xA9, 0x10, 0x8D, 0x15, 0x40, // LDA #$10, STA $4015
+        ];
+        prg_rom[0..program.len()].copy_from_slice(&program);
+        // loop: STA $00 thirty times, JMP loop
+        for i in 0..30 {
+            prg_rom[0x14 + i * 2..0x16 + i * 2].copy_from_slice(&[0x85, 0x00]);
+        }
+        prg_rom[0x50..0x53].copy_from_slice(&[0x4C, 0x14, 0x80]);
+        prg_rom[0x7FFA..].copy_from_slice(&[0x00, 0x80, 0x00, 0x80, 0x00, 0x80]);
+        test::test_rom_with(prg_rom, vec![], 0, Mirroring::HORIZONTAL)
+    }
+
+    #[test]
+    fn test_restored_machine_keeps_dmc_timing() {
+        let mut nes_a = Nes::new(dmc_rom());
+        for _ in 0..3 {
+            nes_a.run_frame();
+        }
+        let snapshot = nes_a.save_state();
+
+        let mut nes_b = Nes::new(dmc_rom());
+        nes_b.load_state(&snapshot).unwrap();
+        assert_eq!(nes_b.frame_count(), nes_a.frame_count());
+
+        for _ in 0..3 {
+            nes_a.run_frame();
+            nes_b.run_frame();
+        }
+        assert_eq!(nes_a.save_state(), nes_b.save_state());
+        assert!(nes_a.frame().data == nes_b.frame().data);
+    }
+
+    #[test]
+    fn test_truncated_state_leaves_machine_untouched() {
+        let mut nes = Nes::new(frame_counter_rom());
+        nes.run_frame();
+        let state = nes.save_state();
+        nes.run_frame();
+        let before = nes.save_state();
+
+        // cut into the mapper block, after the CPU and most of the bus parsed fine
+        let truncated = &state[..state.len() - 1];
+        assert!(matches!(
+            nes.load_state(truncated),
+            Err(StateError::Truncated { .. })
+        ));
+        assert_eq!(nes.save_state(), before);
+    }
+
+    #[test]
+    fn test_round_trip_primitives() {
+        let mut writer = StateWriter::new();
+        writer.write_u8(0x12);
+        writer.write_bool(true);
+        writer.write_u16(0x3456);
+        writer.write_u32(0x789A_BCDE);
+        writer.write_u64(0x1122_3344_5566_7788);
+        writer.write_bytes(&[1, 2, 3]);
+        let data = writer.into_bytes();
+
+        let mut reader = StateReader::new(&data).unwrap();
+        assert_eq!(reader.read_u8(), Ok(0x12));
+        assert_eq!(reader.read_bool(), Ok(true));
+        assert_eq!(reader.read_u16(), Ok(0x3456));
+        assert_eq!(reader.read_u32(), Ok(0x789A_BCDE));
+        assert_eq!(reader.read_u64(), Ok(0x1122_3344_5566_7788));
+        let mut bytes = [0; 3];
+        reader.read_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, [1, 2, 3]);
+        assert_eq!(reader.read_u8(), Err(StateError::Truncated { offset: 28 }));
+    }
+
+    #[test]
+    fn test_rejects_bad_header() {
+        assert_eq!(StateReader::new(&[1, 2]).err(), Some(StateError::BadMagic));
+        assert_eq!(
+            StateReader::new(&[0x4E, 0x45, 0x53, 0x53, 0xFF]).err(),
+            Some(StateError::UnsupportedVersion(0xFF))
+        );
+    }
+
+    #[test]
+    fn test_block_size_mismatch() {
+        let mut writer = StateWriter::new();
+        writer.write_bytes(&[1, 2, 3]);
+        let data = writer.into_bytes();
+
+        let mut reader = StateReader::new(&data).unwrap();
+        let mut bytes = [0; 4];
+        assert_eq!(
+            reader.read_bytes(&mut bytes),
+            Err(StateError::SizeMismatch {
+                offset: 5,
+                expected: 4,
+                actual: 3
+            })
+        );
+    }
+}
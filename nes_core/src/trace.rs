@@ -209,7 +209,6 @@ pub fn trace(cpu: &mut CPU) -> String {
         cpu.stack_pointer
     )
 }
-/*
 #[cfg(test)]
 mod test {
     use super::*;
@@ -276,4 +275,3 @@ mod test {
         );
     }
 }
-*/
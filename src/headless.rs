@@ -0,0 +1,123 @@
+use std::fs;
+use std::path::PathBuf;
+
+use nes_core::headless::{self, InputScript, StopCondition};
+use nes_core::rom::Rom;
+
+const DEFAULT_FRAMES: usize = 600;
+
+const USAGE: &str = "usage: nes_emulator --headless <rom> [--frames N] \
+[--until ADDR==VALUE | --until ADDR!=VALUE] [--input FILE] \
+[--screenshot FILE.ppm] [--ram-dump FILE]";
+
+struct Options {
+    rom_path: PathBuf,
+    frames: usize,
+    until: Option<StopCondition>,
+    input_path: Option<PathBuf>,
+    screenshot_path: Option<PathBuf>,
+    ram_dump_path: Option<PathBuf>,
+}
+
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut args = args.iter();
+    let rom_path = args.next().ok_or("No ROM specified")?;
+    let mut options = Options {
+        rom_path: PathBuf::from(rom_path),
+        frames: DEFAULT_FRAMES,
+        until: None,
+        input_path: None,
+        screenshot_path: None,
+        ram_dump_path: None,
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        match flag.as_str() {
+            "--frames" => {
+                options.frames = value
+                    .parse()
+                    .map_err(|_| format!("bad frame count '{}'", value))?
+            }
+            "--until" => options.until = Some(StopCondition::parse(value)?),
+            "--input" => options.input_path = Some(PathBuf::from(value)),
+            "--screenshot" => options.screenshot_path = Some(PathBuf::from(value)),
+            "--ram-dump" => options.ram_dump_path = Some(PathBuf::from(value)),
+            _ => return Err(format!("unknown option '{}'", flag)),
+        }
+    }
+
+    Ok(options)
+}
+
+// Entry point for `--headless`. Returns the process exit code: 0 when the run
+// finished (and the condition, if any, was met), 1 when the condition was not
+// met in time, 2 on bad arguments or I/O errors.
+pub fn main(args: &[String]) -> i32 {
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("{}\n{}", err, USAGE);
+            return 2;
+        }
+    };
+
+    let rom = match fs::read(&options.rom_path) {
+        Ok(raw) => match Rom::new(&raw) {
+            Ok(rom) => rom,
+            Err(err) => {
+                eprintln!("Cannot load ROM: {}", err);
+                return 2;
+            }
+        },
+        Err(err) => {
+            eprintln!("Cannot open ROM: {}", err);
+            return 2;
+        }
+    };
+
+    let input = match &options.input_path {
+        Some(path) => match fs::read_to_string(path).map_err(|err| err.to_string()) {
+            Ok(text) => match InputScript::parse(&text) {
+                Ok(input) => input,
+                Err(err) => {
+                    eprintln!("Cannot parse input script: {}", err);
+                    return 2;
+                }
+            },
+            Err(err) => {
+                eprintln!("Cannot read input script: {}", err);
+                return 2;
+            }
+        },
+        None => InputScript::empty(),
+    };
+
+    let outcome = headless::run(rom, options.frames, options.until, &input);
+
+    if let Some(path) = &options.screenshot_path {
+        let written =
+            fs::File::create(path).and_then(|mut file| outcome.frame.write_ppm(&mut file));
+        if let Err(err) = written {
+            eprintln!("Cannot write screenshot: {}", err);
+            return 2;
+        }
+    }
+    if let Some(path) = &options.ram_dump_path {
+        if let Err(err) = fs::write(path, &outcome.ram) {
+            eprintln!("Cannot write RAM dump: {}", err);
+            return 2;
+        }
+    }
+
+    println!("Ran {} frames", outcome.frames);
+    match options.until {
+        Some(_) if !outcome.condition_met => {
+            eprintln!("Condition not met after {} frames", outcome.frames);
+            1
+        }
+        _ => 0,
+    }
+}
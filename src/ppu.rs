@@ -1,220 +0,0 @@
-use crate::{
-    ppu_registers::{
-        address_register::AddrRegister, control_register::ControlRegister,
-        mask_register::MaskRegister, scroll_register::ScrollRegister,
-        status_register::StatusRegister,
-    },
-    rom::Mirroring,
-};
-
-pub struct NesPPU {
-    pub chr_rom: Vec<u8>,
-    pub palette_table: [u8; 32],
-    pub vram: [u8; 2048],
-
-    pub mirroring: Mirroring,
-    pub ctrl: ControlRegister,
-
-    pub status: StatusRegister,
-    pub addr: AddrRegister,
-    pub scroll: ScrollRegister,
-    pub mask: MaskRegister,
-
-    pub oam_data: [u8; 256],
-    pub oam_addr: u8,
-
-    pub nmi_interrupt: Option<u8>,
-
-    scanline: u16,
-    cycles: usize,
-    internal_data_buf: u8,
-}
-
-impl NesPPU {
-    pub fn new(chr_rom: Vec<u8>, mirroring: Mirroring) -> Self {
-        NesPPU {
-            chr_rom,
-            mirroring,
-            vram: [0; 2048],
-            oam_data: [0; 64 * 4],
-            palette_table: [0; 32],
-            oam_addr: 0,
-            ctrl: ControlRegister::new(),
-            addr: AddrRegister::new(),
-            status: StatusRegister::new(),
-            scroll: ScrollRegister::new(),
-            mask: MaskRegister::new(),
-            scanline: 0,
-            cycles: 0,
-            internal_data_buf: 0,
-            nmi_interrupt: None,
-        }
-    }
-    // private methods
-    fn increment_vram_addr(&mut self) {
-        self.addr.increment(self.ctrl.vram_addr_increment());
-    }
-
-    fn increment_oam_addr(&mut self) {
-        self.oam_addr = self.oam_addr.wrapping_add(1);
-    }
-
-    pub fn tick(&mut self, cycles: u8) -> bool {
-        self.cycles += cycles as usize;
-        if self.cycles >= 341 {
-            if self.is_sprite_0_hit(self.cycles) {
-                self.status.set_sprite_zero_hit_status(true);
-            }
-
-            self.cycles = self.cycles - 341;
-            self.scanline += 1;
-
-            if self.scanline == 241 {
-                self.status.set_vblank_status(true);
-                self.status.set_sprite_zero_hit_status(false);
-                if self.ctrl.generate_vblank_nmi() {
-                    self.nmi_interrupt = Some(1);
-                }
-            }
-
-            if self.scanline >= 262 {
-                self.scanline = 0;
-                self.nmi_interrupt = None;
-                self.status.set_sprite_zero_hit_status(false);
-                self.status.reset_vblank_status();
-                return true;
-            }
-        }
-        return false;
-    }
-
-    fn is_sprite_0_hit(&self, cycle: usize) -> bool {
-        let y = self.oam_data[0] as usize;
-        let x = self.oam_data[3] as usize;
-        (y == self.scanline as usize) && x <= cycle && self.mask.show_sprites()
-    }
-    pub fn new_empty_rom() -> Self {
-        NesPPU::new(vec![0; 2048], Mirroring::HORIZONTAL)
-    }
-
-    pub fn write_to_ppu_addr(&mut self, value: u8) {
-        self.addr.update(value);
-    }
-
-    pub fn write_to_ctrl(&mut self, value: u8) {
-        let before_nmi_status = self.ctrl.generate_vblank_nmi();
-        self.ctrl.update(value);
-        if !before_nmi_status && self.ctrl.generate_vblank_nmi() && self.status.is_in_vblank() {
-            self.nmi_interrupt = Some(1);
-        }
-    }
-
-    pub fn write_to_data(&mut self, value: u8) {
-        let addr = self.addr.get();
-        match addr {
-            0..=0x1fff => {
-                panic!("Attemping to write to chr_rom register")
-            }
-            0x2000..=0x2fff => {
-                self.vram[self.mirror_vram_addr(addr) as usize] = value;
-            }
-            0x3000..=0x3eff => {
-                panic!(
-                    "cannot write to {}, addr space 0x3000..0x3eff is not expected to be used",
-                    addr
-                )
-            }
-            0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
-                let add_mirror = addr - 0x10;
-                self.palette_table[(add_mirror - 0x3f00) as usize] = value;
-            }
-            0x3f00..=0x3fff => self.palette_table[(addr - 0x3f00) as usize] = value,
-            _ => panic!("unexpected access to mirrored space {}", addr),
-        }
-        self.increment_vram_addr();
-    }
-
-    pub fn read_data(&mut self) -> u8 {
-        let addr = self.addr.get();
-        self.increment_vram_addr();
-
-        match addr {
-            0..=0x1fff => {
-                let result = self.internal_data_buf;
-                self.internal_data_buf = self.chr_rom[addr as usize];
-                result
-            }
-            0x2000..=0x2fff => {
-                let result = self.internal_data_buf;
-                self.internal_data_buf = self.vram[self.mirror_vram_addr(addr) as usize];
-                result
-            }
-            0x3000..=0x3eff => panic!(
-                "addr space 0x3000..0x3eff is not expected to be used, requested = {} ",
-                addr
-            ),
-            0x3f10 | 0x3f14 | 0x3f18 | 0x3f1c => {
-                let add_mirror = addr - 0x10;
-                self.palette_table[(add_mirror - 0x3f00) as usize]
-            }
-            0x3f00..=0x3fff => self.palette_table[(addr - 0x3f00) as usize],
-            _ => panic!("unexpected access to mirrored space {}", addr),
-        }
-    }
-
-    // Horizontal:
-    //   [ A ] [ a ]
-    //   [ B ] [ b ]
-
-    // Vertical:
-    //   [ A ] [ B ]
-    //   [ a ] [ b ]
-    pub fn mirror_vram_addr(&self, addr: u16) -> u16 {
-        let mirrored_vram = addr & 0b10111111111111; // mirror down 0x3000-0x3eff to 0x2000 - 0x2eff
-        let vram_index = mirrored_vram - 0x2000; // to vram vector
-        let name_table = vram_index / 0x400; // to the name table index
-        match (&self.mirroring, name_table) {
-            (Mirroring::VERTICAL, 2) | (Mirroring::VERTICAL, 3) => vram_index - 0x800,
-            (Mirroring::HORIZONTAL, 2) => vram_index - 0x400,
-            (Mirroring::HORIZONTAL, 1) => vram_index - 0x400,
-            (Mirroring::HORIZONTAL, 3) => vram_index - 0x800,
-            _ => vram_index,
-        }
-    }
-
-    pub fn read_status(&mut self) -> u8 {
-        let data = self.status.snapshot();
-        self.status.reset_vblank_status();
-        self.addr.reset_latch();
-        self.scroll.reset_latch();
-        data
-    }
-
-    pub fn write_to_oam_addr(&mut self, value: u8) {
-        self.oam_addr = value;
-    }
-
-    pub fn write_to_oam_data(&mut self, value: u8) {
-        self.oam_data[self.oam_addr as usize] = value;
-        self.increment_oam_addr();
-    }
-
-    pub fn write_oam_dma(&mut self, data: &[u8; 256]) {
-        for datum in data {
-            self.oam_data[self.oam_addr as usize] = *datum;
-            self.increment_oam_addr();
-        }
-    }
-
-    pub fn read_oam_data(&mut self) -> u8 {
-        self.oam_data[self.oam_addr as usize]
-    }
-
-    pub fn write_to_scroll(&mut self, data: u8) {
-        self.scroll.write(data);
-    }
-
-    pub fn write_to_mask(&mut self, data: u8) {
-        self.mask.update(data);
-    }
-}
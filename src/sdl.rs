@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use nes_core::joypad::JoypadButton;
+use nes_core::nes::Nes;
+use nes_core::rewind::RewindBuffer;
+use nes_core::rom::Rom;
+use nes_core::save_file::{self, SaveFile};
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::pixels::PixelFormatEnum;
+
+// flush battery-backed RAM roughly every 5 seconds
+const SAVE_FLUSH_INTERVAL: u32 = 300;
+
+// one snapshot per frame, 10 seconds of history
+const REWIND_CAPACITY: usize = 600;
+
+pub fn run(rom_path: &Path) {
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+
+    let window = video_subsystem
+        .window("NES Emulator", (256.0 * 3.0) as u32, (240.0 * 3.0) as u32)
+        .position_centered()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    canvas.set_scale(3.0, 3.0).unwrap();
+
+    let creator = canvas.texture_creator();
+    let mut texture = creator
+        .create_texture_target(PixelFormatEnum::RGB24, 256, 240)
+        .unwrap();
+
+    let mut key_map = HashMap::new();
+    key_map.insert(Keycode::Down, JoypadButton::DOWN);
+    key_map.insert(Keycode::Up, JoypadButton::UP);
+    key_map.insert(Keycode::Right, JoypadButton::RIGHT);
+    key_map.insert(Keycode::Left, JoypadButton::LEFT);
+    key_map.insert(Keycode::Space, JoypadButton::SELECT);
+    key_map.insert(Keycode::Return, JoypadButton::START);
+    key_map.insert(Keycode::A, JoypadButton::BUTTON_A);
+    key_map.insert(Keycode::S, JoypadButton::BUTTON_B);
+
+    let mut rom = File::open(rom_path).expect("Cannot open ROM");
+    let mut rom_buffer = Vec::new();
+    rom.read_to_end(&mut rom_buffer).unwrap();
+
+    // load the game
+    let rom = match Rom::new(&rom_buffer) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Cannot load ROM: {}", err);
+            return;
+        }
+    };
+    let mut nes = Nes::new(rom);
+
+    let save_path = save_file::save_path(rom_path);
+    let loaded = match save_file::load(&save_path, &mut *nes.mapper().borrow_mut()) {
+        Ok(loaded) => loaded,
+        Err(err) => {
+            eprintln!("Cannot read save file: {}", err);
+            Vec::new()
+        }
+    };
+    let mut save_file = SaveFile::new(save_path, loaded);
+
+    // hold backspace to step back one frame per rendered frame
+    let mut rewind_buffer = RewindBuffer::new(REWIND_CAPACITY);
+    let mut rewinding = false;
+    let mut frame_count: u32 = 0;
+
+    // the game cycle
+    loop {
+        // snapshots are taken at the start of each frame, so the one before
+        // the newest starts the frame before the one on screen: loading it and
+        // running a frame steps back exactly one frame
+        if !rewinding {
+            rewind_buffer.push(nes.save_state());
+        } else if let Some(state) = rewind_buffer.rewind() {
+            if let Err(err) = nes.load_state(&state) {
+                eprintln!("Cannot rewind: {}", err);
+            }
+        }
+
+        match nes.run_frame() {
+            Some(frame) => texture.update(None, &frame.data, 256 * 3).unwrap(),
+            None => {
+                // the CPU hit BRK: there is nothing left to run
+                eprintln!("CPU halted");
+                flush_save_file(&mut save_file, &nes);
+                return;
+            }
+        }
+        canvas.copy(&texture, None, None).unwrap();
+        canvas.present();
+
+        frame_count = frame_count.wrapping_add(1);
+        if frame_count % SAVE_FLUSH_INTERVAL == 0 {
+            flush_save_file(&mut save_file, &nes);
+        }
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    flush_save_file(&mut save_file, &nes);
+                    return;
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
+                Event::KeyDown { keycode, .. } => {
+                    if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                        nes.set_button(*key, true);
+                    }
+                }
+                Event::KeyUp { keycode, .. } => {
+                    if let Some(key) = key_map.get(&keycode.unwrap_or(Keycode::Ampersand)) {
+                        nes.set_button(*key, false);
+                    }
+                }
+                _ => { /* do nothing */ }
+            }
+        }
+    }
+
+    /*while nes.step_instruction() {
+        println!("{}", nes_core::trace::trace(nes.cpu_mut()));
+        // ::std::thread::sleep(std::time::Duration::new(0, 70_000));
+    }*/
+}
+
+fn flush_save_file(save_file: &mut SaveFile, nes: &Nes) {
+    if let Err(err) = save_file.flush(&*nes.mapper().borrow()) {
+        eprintln!("Cannot write save file: {}", err);
+    }
+}